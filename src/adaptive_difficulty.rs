@@ -0,0 +1,158 @@
+//
+// Adaptive difficulty
+//
+// Optional, off unless `Tuning::adaptive_difficulty` is above 0 (e.g. with
+// `adaptive_difficulty = 1` in a `--tuning` preset). Once a second, gravity is
+// nudged towards a target that depends on how the player is doing:
+//  * a tall stack lowers gravity, an empty board raises it
+//  * placing pieces faster than `TARGET_PIECES_PER_SECOND` raises it
+//
+// Being a tuning value, it's part of the replay config hash, so replays of
+// adaptive games can't be mistaken for regular ones. Time is counted in physics
+// steps, so gravity changes on the same tick when the game is replayed.
+//
+// There are no garbage rows yet; their frequency can adapt the same way once
+// they exist.
+//
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::dynamics::IntegrationParameters;
+use bevy_rapier2d::rapier::math::Vector;
+
+use crate::board_grid::BoardGrid;
+use crate::toast::{Toast, ToastIcon};
+use crate::{Game, GameState, Restart, Tuning, RESTART};
+
+const TARGET_PIECES_PER_SECOND: f32 = 0.5;
+
+/// Recent pace is measured over this many seconds of play
+const PACE_WINDOW: f32 = 30.0;
+
+/// Seconds of play between gravity updates
+const UPDATE_INTERVAL: f32 = 1.0;
+
+const MIN_GRAVITY_FACTOR: f32 = 0.5;
+const MAX_GRAVITY_FACTOR: f32 = 1.5;
+
+/// How much of the way to the target gravity is covered per update
+const GRAVITY_EASING: f32 = 0.2;
+
+/// Resource: Res<AdaptiveDifficulty>
+struct AdaptiveDifficulty {
+    /// Gravity before adapting
+    base_gravity: Vector<f32>,
+    gravity_factor: f32,
+    /// Seconds of play since gravity was last updated
+    since_update: f32,
+    /// Seconds of play so far
+    play_time: f32,
+    /// Play time at each recent spawn, oldest first
+    recent_spawns: VecDeque<f32>,
+    n_pieces_seen: usize,
+}
+
+pub struct AdaptiveDifficultyPlugin;
+
+impl Plugin for AdaptiveDifficultyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup_adaptive_difficulty.system())
+            .add_system(reset_adaptive_difficulty.system().after(RESTART))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(adapt_difficulty.system()),
+            );
+    }
+}
+
+// startup system
+fn setup_adaptive_difficulty(
+    mut commands: Commands,
+    tuning: Res<Tuning>,
+    rapier_config: Res<RapierConfiguration>,
+    mut toasts: EventWriter<Toast>,
+) {
+    if tuning.adaptive_difficulty <= 0.0 {
+        return;
+    }
+
+    commands.insert_resource(AdaptiveDifficulty {
+        base_gravity: rapier_config.gravity,
+        gravity_factor: 1.0,
+        since_update: 0.0,
+        play_time: 0.0,
+        recent_spawns: VecDeque::new(),
+        n_pieces_seen: 0,
+    });
+    toasts.send(Toast::new(ToastIcon::Info, "Adaptive difficulty is on"));
+}
+
+// system
+fn reset_adaptive_difficulty(
+    mut restart_events: EventReader<Restart>,
+    adaptive: Option<ResMut<AdaptiveDifficulty>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    let mut adaptive = match adaptive {
+        Some(adaptive) => adaptive,
+        None => return,
+    };
+
+    if restart_events.iter().count() > 0 {
+        adaptive.gravity_factor = 1.0;
+        adaptive.since_update = 0.0;
+        adaptive.play_time = 0.0;
+        adaptive.recent_spawns.clear();
+        adaptive.n_pieces_seen = 0;
+        rapier_config.gravity = adaptive.base_gravity;
+    }
+}
+
+// system
+fn adapt_difficulty(
+    integration_parameters: Res<IntegrationParameters>,
+    game: Res<Game>,
+    grid: Res<BoardGrid>,
+    tuning: Res<Tuning>,
+    adaptive: Option<ResMut<AdaptiveDifficulty>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    let mut adaptive = match adaptive {
+        Some(adaptive) => adaptive,
+        None => return,
+    };
+
+    adaptive.play_time += integration_parameters.dt;
+    adaptive.since_update += integration_parameters.dt;
+    let play_time = adaptive.play_time;
+
+    while adaptive.n_pieces_seen < game.n_pieces {
+        adaptive.recent_spawns.push_back(play_time);
+        adaptive.n_pieces_seen += 1;
+    }
+    while let Some(spawn_time) = adaptive.recent_spawns.front().copied() {
+        if spawn_time >= play_time - PACE_WINDOW {
+            break;
+        }
+        adaptive.recent_spawns.pop_front();
+    }
+
+    if adaptive.since_update < UPDATE_INTERVAL {
+        return;
+    }
+    adaptive.since_update -= UPDATE_INTERVAL;
+
+    // 0 for an empty board, 1 for a full one
+    let stack_height =
+        grid.column_heights().into_iter().max().unwrap_or(0) as f32 / game.n_rows.max(1) as f32;
+    let pieces_per_second = adaptive.recent_spawns.len() as f32 / play_time.min(PACE_WINDOW);
+    // 0 on target, positive when ahead of it
+    let pace = (pieces_per_second / TARGET_PIECES_PER_SECOND).min(2.0) - 1.0;
+
+    let target_factor = (1.0 + tuning.adaptive_difficulty * (0.5 * pace - (stack_height - 0.5)))
+        .clamp(MIN_GRAVITY_FACTOR, MAX_GRAVITY_FACTOR);
+
+    adaptive.gravity_factor += (target_factor - adaptive.gravity_factor) * GRAVITY_EASING;
+    rapier_config.gravity = adaptive.base_gravity * adaptive.gravity_factor;
+}
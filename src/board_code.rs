@@ -0,0 +1,328 @@
+//
+// Board codes
+//
+// A code for the current position, to discuss it with friends or practice it:
+// the occupancy grid (see board_grid.rs), the current tetromino and the next
+// ones in the queue. F10 prints the code to the log. `--board-code <code>`
+// starts every game on that board, and the first game deals the pieces in the
+// same order before carrying on with random ones.
+//
+// The stack is restored as the grid saw it, with each block straightened out
+// in the cell its center was in, so it won't be exactly as the physics had it.
+// The current tetromino is restored at the top of the board, not where it was.
+//
+// The board size, the stack and the dealt pieces are all part of the replay
+// config hash, so a replay or share code from a game started with
+// `--board-code` needs the same code to play back.
+//
+// Layout before base32 encoding (see share_code.rs):
+//
+//   version        u8
+//   board size     u8 lanes, u8 rows
+//   piece count    u8, then the index of each kind in `TetrominoKind::ALL`,
+//                  current tetromino first
+//   cells          one bit per cell, row by row from the bottom, first cell in
+//                  the most significant bit
+//   checksum       u8, catches typos
+//
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use bevy::prelude::*;
+
+use crate::board_grid::BoardGrid;
+use crate::share_code::{checksum, decode_base32, encode_base32};
+use crate::toast::{Toast, ToastIcon};
+use crate::{Game, TetrominoKind, MIN_LANES, MIN_ROWS};
+
+const VERSION: u8 = 1;
+
+const PRINT_KEY: KeyCode = KeyCode::F10;
+
+/// Pieces in the code, counting the current tetromino
+const N_PIECES: usize = 6;
+
+pub struct BoardCode {
+    pub n_lanes: usize,
+    pub n_rows: usize,
+    pub pieces: Vec<TetrominoKind>,
+    pub grid: BoardGrid,
+}
+
+#[derive(Debug)]
+pub enum BoardCodeError {
+    InvalidCharacter(char),
+    WrongLength,
+    Mistyped,
+    UnsupportedVersion { found: u8 },
+    UnknownPiece(u8),
+    UnplayableSize { n_lanes: usize, n_rows: usize },
+}
+
+impl fmt::Display for BoardCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidCharacter(c) => write!(f, "'{}' can't be part of a board code", c),
+            Self::WrongLength => write!(f, "board code has the wrong length"),
+            Self::Mistyped => write!(f, "board code is mistyped"),
+            Self::UnsupportedVersion { found } => write!(
+                f,
+                "board code is from a newer version of the game (code version {})",
+                found
+            ),
+            Self::UnknownPiece(index) => write!(f, "board code has an unknown piece {}", index),
+            Self::UnplayableSize { n_lanes, n_rows } => write!(
+                f,
+                "board code is for a {}x{} board, the game needs at least {}x{}",
+                n_lanes, n_rows, MIN_LANES, MIN_ROWS
+            ),
+        }
+    }
+}
+
+impl BoardCode {
+    ///
+    /// The code for the current position. None if the board is too big to fit
+    /// in a code.
+    ///
+    pub fn new(game: &mut Game, grid: &BoardGrid) -> Option<Self> {
+        u8::try_from(game.n_lanes).ok()?;
+        u8::try_from(game.n_rows).ok()?;
+
+        let mut pieces: Vec<TetrominoKind> = game.current_tetromino_kind.into_iter().collect();
+        pieces.extend(game.pieces.peek(N_PIECES - pieces.len()));
+
+        Some(Self {
+            n_lanes: game.n_lanes,
+            n_rows: game.n_rows,
+            pieces,
+            grid: grid.clone(),
+        })
+    }
+
+    pub fn parse(code: &str) -> Result<Self, BoardCodeError> {
+        let bytes = decode_base32(code).map_err(BoardCodeError::InvalidCharacter)?;
+        if bytes.len() < 5 {
+            return Err(BoardCodeError::WrongLength);
+        }
+        let (checksum_byte, bytes) = bytes.split_last().unwrap();
+        if checksum(bytes) != *checksum_byte {
+            return Err(BoardCodeError::Mistyped);
+        }
+        if bytes[0] != VERSION {
+            return Err(BoardCodeError::UnsupportedVersion { found: bytes[0] });
+        }
+
+        let n_lanes = bytes[1] as usize;
+        let n_rows = bytes[2] as usize;
+        if n_lanes < MIN_LANES || n_rows < MIN_ROWS {
+            return Err(BoardCodeError::UnplayableSize { n_lanes, n_rows });
+        }
+        let n_pieces = bytes[3] as usize;
+        let cells_start = 4 + n_pieces;
+        // Base32 can leave a few bits over, but never a whole byte
+        if bytes.len() != cells_start + (n_lanes * n_rows + 7) / 8 {
+            return Err(BoardCodeError::WrongLength);
+        }
+
+        let pieces = bytes[4..cells_start]
+            .iter()
+            .map(|index| {
+                TetrominoKind::ALL
+                    .get(*index as usize)
+                    .copied()
+                    .ok_or(BoardCodeError::UnknownPiece(*index))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut grid = BoardGrid::new(n_lanes, n_rows);
+        for i in 0..n_lanes * n_rows {
+            if bytes[cells_start + i / 8] & (0x80 >> (i % 8)) != 0 {
+                grid.set_occupied(((i % n_lanes) as i32, (i / n_lanes) as i32));
+            }
+        }
+
+        Ok(Self {
+            n_lanes,
+            n_rows,
+            pieces,
+            grid,
+        })
+    }
+
+    ///
+    /// Set up a new game for the position: board size and the pieces to come.
+    /// The stack is filled in from `grid` by board_grid.rs.
+    ///
+    pub fn apply(&self, game: &mut Game) {
+        game.n_lanes = self.n_lanes;
+        game.n_rows = self.n_rows;
+        game.pieces.deal_first(&self.pieces);
+        game.dealt_first = self.pieces.clone();
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![
+            VERSION,
+            self.n_lanes as u8,
+            self.n_rows as u8,
+            self.pieces.len() as u8,
+        ];
+        bytes.extend(self.pieces.iter().map(|kind| *kind as u8));
+
+        let mut cells = vec![0; (self.n_lanes * self.n_rows + 7) / 8];
+        for i in 0..self.n_lanes * self.n_rows {
+            if self
+                .grid
+                .is_occupied(((i % self.n_lanes) as i32, (i / self.n_lanes) as i32))
+            {
+                cells[i / 8] |= 0x80 >> (i % 8);
+            }
+        }
+        bytes.extend(cells);
+
+        bytes.push(checksum(&bytes));
+        bytes
+    }
+}
+
+impl fmt::Display for BoardCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", encode_base32(&self.to_bytes()))
+    }
+}
+
+///
+/// `--board-code <code>`. Exits the process with a readable message if the code
+/// is invalid.
+///
+pub fn from_args() -> Option<BoardCode> {
+    let code = crate::arg_value("--board-code")?;
+
+    match BoardCode::parse(&code) {
+        Ok(board_code) => Some(board_code),
+        Err(err) => {
+            eprintln!("Can't use board code {}: {}", code, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+pub struct BoardCodePlugin;
+
+impl Plugin for BoardCodePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(print_board_code.system());
+    }
+}
+
+// system
+fn print_board_code(
+    input: Res<Input<KeyCode>>,
+    mut game: ResMut<Game>,
+    grid: Res<BoardGrid>,
+    mut toasts: EventWriter<Toast>,
+) {
+    if !input.just_pressed(PRINT_KEY) {
+        return;
+    }
+
+    match BoardCode::new(&mut game, &grid) {
+        Some(board_code) => {
+            println!("Board code: {}", board_code);
+            toasts.send(Toast::new(ToastIcon::Info, "Board code printed to the log"));
+        }
+        None => toasts.send(Toast::new(
+            ToastIcon::Info,
+            "The board is too big for a code",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::config_hash;
+    use crate::Tuning;
+
+    fn board_code(n_lanes: usize, n_rows: usize) -> BoardCode {
+        let mut grid = BoardGrid::new(n_lanes, n_rows);
+        grid.set_occupied((0, 0));
+        grid.set_occupied((1, 0));
+        grid.set_occupied((n_lanes as i32 - 1, 1));
+
+        BoardCode {
+            n_lanes,
+            n_rows,
+            pieces: vec![TetrominoKind::T, TetrominoKind::I, TetrominoKind::Z],
+            grid,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let code = board_code(10, 20);
+        let parsed = BoardCode::parse(&code.to_string()).unwrap();
+
+        assert_eq!(parsed.n_lanes, code.n_lanes);
+        assert_eq!(parsed.n_rows, code.n_rows);
+        assert_eq!(parsed.pieces, code.pieces);
+        assert_eq!(parsed.grid, code.grid);
+    }
+
+    #[test]
+    fn mistyped() {
+        let mut typo = board_code(10, 20).to_string().into_bytes();
+        typo[6] = if typo[6] == b'A' { b'B' } else { b'A' };
+
+        assert!(matches!(
+            BoardCode::parse(std::str::from_utf8(&typo).unwrap()),
+            Err(BoardCodeError::Mistyped)
+        ));
+    }
+
+    #[test]
+    fn checksum_depends_on_order() {
+        assert_eq!(checksum(&[]), 0);
+        assert_ne!(checksum(&[1, 2]), checksum(&[2, 1]));
+    }
+
+    #[test]
+    fn unplayable_sizes() {
+        for (n_lanes, n_rows) in [(0, 20), (10, 0), (3, 20), (10, 3)].iter() {
+            assert!(matches!(
+                BoardCode::parse(&board_code(*n_lanes, *n_rows).to_string()),
+                Err(BoardCodeError::UnplayableSize { .. })
+            ));
+        }
+        assert!(BoardCode::parse(&board_code(MIN_LANES, MIN_ROWS).to_string()).is_ok());
+    }
+
+    #[test]
+    fn applied_code_is_part_of_config_hash() {
+        let tuning = Tuning::default();
+        let code = board_code(8, 16);
+
+        let mut game = Game::with_seed(7);
+        let plain_hash = config_hash(&game, &tuning);
+        code.apply(&mut game);
+        assert_eq!((game.n_lanes, game.n_rows), (8, 16));
+        assert_ne!(config_hash(&game, &tuning), plain_hash);
+
+        // Same size, other pieces
+        let mut other_pieces = Game::with_seed(7);
+        BoardCode {
+            pieces: vec![TetrominoKind::O],
+            ..board_code(8, 16)
+        }
+        .apply(&mut other_pieces);
+        assert_ne!(
+            config_hash(&other_pieces, &tuning),
+            config_hash(&game, &tuning)
+        );
+
+        // The dealt pieces only apply to the first game
+        assert_eq!(game.restarted().dealt_first, vec![]);
+    }
+}
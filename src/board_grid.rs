@@ -0,0 +1,410 @@
+//
+// Occupancy grid
+//
+// Which cells of the board hold a block that is part of the stack, i.e. any
+// block but the current tetromino. Blocks don't sit neatly in cells, so a block
+// occupies the cell its center is in.
+//
+// Anything interested in the shape of the stack should use the methods here
+// instead of looking at block positions itself.
+//
+// Grids have a text form, one line per row from the top down, with the letter
+// of the tetromino kind a block came from in its cell, `#` for a block that
+// didn't come from a tetromino, and `.` for an empty cell. F9 prints the
+// current grid to the log, and `--board <file>` starts every game with blocks
+// in the occupied cells of the file, e.g. for puzzles or test fixtures. Its
+// rows are placed from the floor up. `--board-code` does the same from a board
+// code, see board_code.rs. The starting board is part of the replay config
+// hash, so replays have to be played back with the same `--board`.
+//
+
+use std::fs;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{Block, Game, IVector, Restart, TetrominoKind, Tuning, BOARD_GRID, LOCK, RESTART};
+
+const PRINT_KEY: KeyCode = KeyCode::F9;
+
+/// What a cell holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cell {
+    Empty,
+    /// A block, and the kind of tetromino it came from, if any
+    Occupied(Option<TetrominoKind>),
+}
+
+/// Resource: Res<BoardGrid>
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoardGrid {
+    n_lanes: usize,
+    n_rows: usize,
+    /// Row major, starting with the bottom row
+    cells: Vec<Cell>,
+}
+
+impl BoardGrid {
+    pub fn new(n_lanes: usize, n_rows: usize) -> Self {
+        Self {
+            n_lanes,
+            n_rows,
+            cells: vec![Cell::Empty; n_lanes * n_rows],
+        }
+    }
+
+    pub fn is_occupied(&self, coord: IVector) -> bool {
+        self.cell(coord) != Cell::Empty
+    }
+
+    ///
+    /// Occupy a cell with a block that didn't come from a tetromino
+    ///
+    pub fn set_occupied(&mut self, coord: IVector) {
+        self.set_block(coord, None);
+    }
+
+    pub fn set_block(&mut self, (col, row): IVector, kind: Option<TetrominoKind>) {
+        if let Some(index) = self.index(col, row) {
+            self.cells[index] = Cell::Occupied(kind);
+        }
+    }
+
+    fn cell(&self, (col, row): IVector) -> Cell {
+        self.index(col, row)
+            .map(|index| self.cells[index])
+            .unwrap_or(Cell::Empty)
+    }
+
+    fn index(&self, col: i32, row: i32) -> Option<usize> {
+        if col < 0 || row < 0 || col as usize >= self.n_lanes || row as usize >= self.n_rows {
+            None
+        } else {
+            Some(row as usize * self.n_lanes + col as usize)
+        }
+    }
+
+    ///
+    /// One line per row, top row first
+    ///
+    pub fn to_ascii(&self) -> String {
+        (0..self.n_rows as i32)
+            .rev()
+            .map(|row| {
+                (0..self.n_lanes as i32)
+                    .map(|col| match self.cell((col, row)) {
+                        Cell::Empty => '.',
+                        Cell::Occupied(None) => '#',
+                        Cell::Occupied(Some(kind)) => kind.letter(),
+                    })
+                    .chain(std::iter::once('\n'))
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    ///
+    /// Read the format written by `to_ascii`. Blank lines are skipped.
+    /// `None` if the lines have different lengths or contain anything else.
+    ///
+    pub fn from_ascii(text: &str) -> Option<Self> {
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        let n_lanes = lines.first()?.chars().count();
+        let mut grid = Self::new(n_lanes, lines.len());
+
+        for (row, line) in lines.iter().rev().enumerate() {
+            if line.chars().count() != n_lanes {
+                return None;
+            }
+            for (col, cell) in line.chars().enumerate() {
+                let coord = (col as i32, row as i32);
+                match cell {
+                    '.' => {}
+                    '#' => grid.set_occupied(coord),
+                    letter => grid.set_block(coord, Some(TetrominoKind::from_letter(letter)?)),
+                }
+            }
+        }
+
+        Some(grid)
+    }
+
+    ///
+    /// For each column, the number of rows up to and including its topmost
+    /// occupied cell. 0 for an empty column.
+    ///
+    pub fn column_heights(&self) -> Vec<usize> {
+        (0..self.n_lanes as i32)
+            .map(|col| {
+                (0..self.n_rows as i32)
+                    .rev()
+                    .find(|row| self.is_occupied((col, *row)))
+                    .map(|row| row as usize + 1)
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
+    ///
+    /// Rows with every cell occupied, bottom up
+    ///
+    pub fn full_rows(&self) -> Vec<i32> {
+        (0..self.n_rows as i32)
+            .filter(|row| (0..self.n_lanes as i32).all(|col| self.is_occupied((col, *row))))
+            .collect()
+    }
+
+    ///
+    /// Empty cells with an occupied cell somewhere above them in the same column
+    ///
+    // Only the debug overlay uses these, see debug_overlay.rs
+    #[cfg(feature = "debug-overlay")]
+    pub fn holes(&self) -> usize {
+        self.column_heights()
+            .iter()
+            .enumerate()
+            .map(|(col, height)| {
+                (0..*height as i32)
+                    .filter(|row| !self.is_occupied((col as i32, *row)))
+                    .count()
+            })
+            .sum()
+    }
+
+    ///
+    /// Sum of height differences between neighbouring columns
+    ///
+    #[cfg(feature = "debug-overlay")]
+    pub fn bumpiness(&self) -> usize {
+        self.column_heights()
+            .windows(2)
+            .map(|pair| (pair[0] as i32 - pair[1] as i32).abs() as usize)
+            .sum()
+    }
+}
+
+///
+/// The `--board` or `--board-code` every game starts with, if any. Stored in
+/// `Game::starting_board`.
+///
+pub fn starting_board_from_args() -> Option<BoardGrid> {
+    let starting_board = crate::arg_value("--board").and_then(|path| {
+        let grid = fs::read_to_string(&path)
+            .map_err(|err| println!("Can't read board {}: {}", path, err))
+            .ok()?;
+
+        BoardGrid::from_ascii(&grid).or_else(|| {
+            println!(
+                "Can't parse board {}, expected lines of ., # and tetromino letters",
+                path
+            );
+            None
+        })
+    });
+
+    starting_board.or_else(|| crate::board_code::from_args().map(|code| code.grid))
+}
+
+pub struct BoardGridPlugin;
+
+impl Plugin for BoardGridPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup_board_grid.system())
+            .add_system(fill_starting_board_on_restart.system().after(RESTART))
+            // After a lock, so the locked tetromino is part of the stack right away
+            .add_system(update_board_grid.system().label(BOARD_GRID).after(LOCK))
+            .add_system(print_board_grid.system());
+    }
+}
+
+// startup system
+fn setup_board_grid(mut commands: Commands, game: Res<Game>, tuning: Res<Tuning>) {
+    commands.insert_resource(BoardGrid::new(game.n_lanes, game.n_rows));
+
+    fill_starting_board(&mut commands, &game, &tuning);
+}
+
+// system
+fn fill_starting_board_on_restart(
+    mut commands: Commands,
+    mut restart_events: EventReader<Restart>,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+) {
+    if restart_events.iter().count() > 0 {
+        fill_starting_board(&mut commands, &game, &tuning);
+    }
+}
+
+fn fill_starting_board(commands: &mut Commands, game: &Game, tuning: &Tuning) {
+    if let Some(grid) = &game.starting_board {
+        for row in 0..grid.n_rows.min(game.n_rows) as i32 {
+            for col in 0..grid.n_lanes.min(game.n_lanes) as i32 {
+                if let Cell::Occupied(kind) = grid.cell((col, row)) {
+                    let block = Block { kind, piece: None };
+                    crate::spawn_board_block(commands, game, tuning, block, (col, row));
+                }
+            }
+        }
+    }
+}
+
+// system
+fn update_board_grid(
+    game: Res<Game>,
+    mut grid: ResMut<BoardGrid>,
+    block_query: Query<(Entity, &RigidBodyPosition, &Block)>,
+) {
+    let mut new_grid = BoardGrid::new(game.n_lanes, game.n_rows);
+
+    for (block_entity, position, block) in block_query.iter() {
+        if !game.current_tetromino_blocks.contains(&block_entity) {
+            let translation = position.position.translation;
+            new_grid.set_block(
+                game.physics_to_board((translation.x, translation.y)),
+                block.kind,
+            );
+        }
+    }
+
+    // Only write when something moved, so consumers can use change detection
+    if *grid != new_grid {
+        *grid = new_grid;
+    }
+}
+
+// system
+fn print_board_grid(input: Res<Input<KeyCode>>, grid: Res<BoardGrid>) {
+    if input.just_pressed(PRINT_KEY) {
+        println!("Board:\n{}", grid.to_ascii());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> BoardGrid {
+        BoardGrid::from_ascii(text).unwrap()
+    }
+
+    #[test]
+    fn ascii_round_trip() {
+        let text = "\
+            ..T...\n\
+            .TTT.#\n\
+            IIIIOO\n\
+            ZZ#.OO\n";
+        let grid = parse(text);
+
+        assert_eq!(grid.to_ascii(), text);
+        assert_eq!(parse(&grid.to_ascii()), grid);
+    }
+
+    #[test]
+    fn ascii_keeps_kinds() {
+        let grid = parse(
+            "
+            S.
+            #.
+            ",
+        );
+
+        assert_eq!(grid.cell((0, 1)), Cell::Occupied(Some(TetrominoKind::S)));
+        assert_eq!(grid.cell((0, 0)), Cell::Occupied(None));
+        assert_eq!(grid.cell((1, 0)), Cell::Empty);
+    }
+
+    #[test]
+    fn ascii_rejects_unknown_cells() {
+        assert_eq!(BoardGrid::from_ascii("..X.\n"), None);
+        assert_eq!(BoardGrid::from_ascii("...\n..\n"), None);
+    }
+
+    #[test]
+    fn empty_grid() {
+        let grid = BoardGrid::new(4, 3);
+
+        assert_eq!(grid.column_heights(), vec![0, 0, 0, 0]);
+        assert_eq!(grid.holes(), 0);
+        assert_eq!(grid.bumpiness(), 0);
+    }
+
+    #[test]
+    fn column_heights() {
+        let grid = parse(
+            "
+            .#..
+            .#..
+            ##.#
+            ",
+        );
+
+        assert_eq!(grid.column_heights(), vec![1, 3, 0, 1]);
+    }
+
+    #[test]
+    fn full_rows() {
+        let grid = parse(
+            "
+            ###
+            #.#
+            ###
+            ",
+        );
+
+        assert_eq!(grid.full_rows(), vec![0, 2]);
+        assert!(BoardGrid::new(3, 2).full_rows().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "debug-overlay")]
+    fn holes_are_empty_cells_under_a_block() {
+        let grid = parse(
+            "
+            ##..
+            .#..
+            #..#
+            ",
+        );
+
+        // One in each of the first two columns, none under the last one
+        assert_eq!(grid.holes(), 2);
+
+        let overhang = parse(
+            "
+            .#.
+            ...
+            ...
+            ",
+        );
+        assert_eq!(overhang.holes(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-overlay")]
+    fn bumpiness() {
+        let grid = parse(
+            "
+            .#..
+            .#..
+            ##.#
+            ",
+        );
+
+        // |1 - 3| + |3 - 0| + |0 - 1|
+        assert_eq!(grid.bumpiness(), 6);
+
+        let flat = parse(
+            "
+            ####
+            ",
+        );
+        assert_eq!(flat.bumpiness(), 0);
+    }
+}
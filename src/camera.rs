@@ -0,0 +1,141 @@
+//
+// Camera following the current tetromino
+//
+// Only kicks in for boards taller than the window (see `--board-size`).
+// Boards that fit keep the camera centered, as before.
+//
+// Also shakes the camera sideways on request, e.g. when the stack collapses.
+//
+
+use bevy::prelude::*;
+
+use crate::{Block, Game, Tuning};
+
+/// Resource: How the camera follows the current tetromino
+pub struct CameraFollow {
+    pub enabled: bool,
+    /// How far (in blocks) the tetromino can be from the center of the view
+    /// before the camera starts to follow
+    pub dead_zone: f32,
+    /// How quickly the camera catches up. Higher is faster.
+    pub stiffness: f32,
+}
+
+impl Default for CameraFollow {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dead_zone: 3.0,
+            stiffness: 4.0,
+        }
+    }
+}
+
+/// Resource: How much the camera shakes, from 0 (still) to 1. Decays by itself.
+#[derive(Default)]
+pub struct CameraShake(pub f32);
+
+/// In pixels, at full shake
+const SHAKE_AMPLITUDE: f32 = 12.0;
+/// In Hz
+const SHAKE_FREQUENCY: f32 = 25.0;
+/// In seconds, from full shake to still
+const SHAKE_DURATION: f32 = 0.6;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(CameraFollow {
+            enabled: !crate::has_flag("--no-camera-follow"),
+            ..CameraFollow::default()
+        })
+        .init_resource::<CameraShake>()
+        .add_system(camera_follow.system())
+        .add_system(camera_shake.system());
+    }
+}
+
+// system
+fn camera_follow(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    follow: Res<CameraFollow>,
+    block_query: Query<&Transform, With<Block>>,
+    mut camera_query: Query<&mut Transform, Without<Block>>,
+) {
+    let (window, camera) = match (windows.get_primary(), game.camera) {
+        (Some(window), Some(camera)) => (window, camera),
+        _ => return,
+    };
+    let mut camera_transform = match camera_query.get_mut(camera) {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    // Screen coordinates of the top of the board and the bottom of the floor
+    let top = -game.floor_y() * tuning.block_px_size;
+    let bottom = (game.floor_y() - tuning.floor_block_height) * tuning.block_px_size;
+    let dead_zone = follow.dead_zone * tuning.block_px_size;
+    let half_view = window.height() * 0.5;
+
+    if !follow.enabled || top - bottom <= 2.0 * half_view {
+        camera_transform.translation.y = 0.0;
+        return;
+    }
+
+    let (y_sum, n_blocks) = game
+        .current_tetromino_blocks
+        .iter()
+        .filter_map(|block_entity| block_query.get(*block_entity).ok())
+        .fold((0.0, 0), |(y_sum, n_blocks), transform| {
+            (y_sum + transform.translation.y, n_blocks + 1)
+        });
+
+    let mut target_y = camera_transform.translation.y;
+    if n_blocks > 0 {
+        let tetromino_y = y_sum / n_blocks as f32;
+        let offset = tetromino_y - target_y;
+
+        if offset.abs() > dead_zone {
+            target_y = tetromino_y - dead_zone * offset.signum();
+        }
+    }
+
+    // Never show anything outside the board
+    let target_y = target_y.clamp(bottom + half_view, top - half_view);
+
+    let catch_up = 1.0 - (-follow.stiffness * time.delta_seconds()).exp();
+    camera_transform.translation.y += (target_y - camera_transform.translation.y) * catch_up;
+}
+
+// system
+fn camera_shake(
+    time: Res<Time>,
+    game: Res<Game>,
+    mut shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, Without<Block>>,
+) {
+    let mut camera_transform = match game
+        .camera
+        .and_then(|camera| camera_query.get_mut(camera).ok())
+    {
+        Some(transform) => transform,
+        None => return,
+    };
+
+    if shake.0 <= 0.0 {
+        if camera_transform.translation.x != 0.0 {
+            camera_transform.translation.x = 0.0;
+        }
+        return;
+    }
+
+    // Deterministic wobble rather than noise; nothing here may use the game's randomness
+    let phase = time.seconds_since_startup() as f32 * SHAKE_FREQUENCY * std::f32::consts::TAU;
+    camera_transform.translation.x = SHAKE_AMPLITUDE * shake.0.powi(2) * phase.sin();
+
+    shake.0 = (shake.0 - time.delta_seconds() / SHAKE_DURATION).max(0.0);
+}
@@ -0,0 +1,83 @@
+//
+// Center of mass marker
+//
+// A small marker at the combined center of mass of the current tetromino's
+// blocks, so players can tell which way the piece will tip when it lands on an
+// edge. Hidden when there is no current tetromino.
+//
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{Game, Tuning};
+
+/// In pixels
+const MARKER_SIZE: f32 = 6.0;
+
+/// In front of the blocks
+const MARKER_Z: f32 = 1.0;
+
+struct CenterOfMassMarker;
+
+pub struct CenterOfMassPlugin;
+
+impl Plugin for CenterOfMassPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup_center_of_mass_marker.system())
+            .add_system(move_center_of_mass_marker.system());
+    }
+}
+
+// startup system
+fn setup_center_of_mass_marker(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(Color::rgb(1.0, 0.2, 0.6).into()),
+            sprite: Sprite::new(Vec2::new(MARKER_SIZE, MARKER_SIZE)),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: false,
+            },
+            ..Default::default()
+        })
+        .insert(CenterOfMassMarker);
+}
+
+// system
+fn move_center_of_mass_marker(
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    mass_query: Query<&RigidBodyMassProps>,
+    mut marker_query: Query<(&mut Transform, &mut Visible), With<CenterOfMassMarker>>,
+) {
+    let (weighted_sum, total_mass) = game
+        .current_tetromino_blocks
+        .iter()
+        .filter_map(|block_entity| mass_query.get(*block_entity).ok())
+        .fold(
+            (Vec2::ZERO, 0.0),
+            |(weighted_sum, total_mass), mass_props| {
+                let mass = mass_props.local_mprops.mass();
+                let com = mass_props.world_com;
+                (
+                    weighted_sum + Vec2::new(com.x, com.y) * mass,
+                    total_mass + mass,
+                )
+            },
+        );
+
+    for (mut transform, mut visible) in marker_query.iter_mut() {
+        let is_visible = total_mass > 0.0;
+        if visible.is_visible != is_visible {
+            visible.is_visible = is_visible;
+        }
+
+        if is_visible {
+            let center = weighted_sum / total_mass * tuning.block_px_size;
+            transform.translation = center.extend(MARKER_Z);
+        }
+    }
+}
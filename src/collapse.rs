@@ -0,0 +1,105 @@
+//
+// Chaos meter and collapse detection
+//
+// The chaos meter measures how far the blocks in the stack (all but the current
+// tetromino) are from sitting neatly in grid cells: per block, the distance to
+// the center of its cell plus the tilt from the nearest right angle, averaged.
+//
+// When it goes over `COLLAPSE_THRESHOLD`, the stack has visibly collapsed, and a
+// `Collapse` event is sent. The camera shakes, a toast announces it, and
+// score.rs adds `Tuning::collapse_points`, a bonus or a penalty depending on
+// the `--feel` preset.
+//
+
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::camera::CameraShake;
+use crate::toast::{Toast, ToastIcon};
+use crate::{Block, Game};
+
+/// Average deviation per block, where 1 is half a block off or 45 degrees tilted
+const COLLAPSE_THRESHOLD: f32 = 0.4;
+
+/// The meter has to drop below this before another collapse can be detected
+const REARM_THRESHOLD: f32 = 0.2;
+
+/// A handful of tumbling blocks is not a collapse
+const MIN_BLOCKS: usize = 8;
+
+/// Event: The stack collapsed
+pub struct Collapse;
+
+/// Resource: Average deviation of stack blocks from their grid cells
+#[derive(Default)]
+pub struct ChaosMeter {
+    pub value: f32,
+    collapsed: bool,
+}
+
+pub struct CollapsePlugin;
+
+impl Plugin for CollapsePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<Collapse>()
+            .init_resource::<ChaosMeter>()
+            .add_system(measure_chaos.system())
+            .add_system(collapse_feedback.system());
+    }
+}
+
+// system
+fn measure_chaos(
+    game: Res<Game>,
+    mut meter: ResMut<ChaosMeter>,
+    mut collapse_events: EventWriter<Collapse>,
+    block_query: Query<(Entity, &RigidBodyPosition), With<Block>>,
+) {
+    let (deviation_sum, n_blocks) = block_query
+        .iter()
+        .filter(|(block_entity, _)| !game.current_tetromino_blocks.contains(block_entity))
+        .map(|(_, position)| {
+            let translation = position.position.translation;
+            let (x, y) =
+                game.board_to_physics(game.physics_to_board((translation.x, translation.y)));
+            let offset = ((x - translation.x).powi(2) + (y - translation.y).powi(2)).sqrt();
+
+            let angle = position.position.rotation.angle();
+            let tilt = (angle - (angle / FRAC_PI_2).round() * FRAC_PI_2).abs();
+
+            offset * 2.0 + tilt / FRAC_PI_4
+        })
+        .fold((0.0, 0), |(sum, n), deviation| (sum + deviation, n + 1));
+
+    let value = if n_blocks > 0 {
+        deviation_sum / n_blocks as f32
+    } else {
+        0.0
+    };
+
+    // Only write when something moved, so consumers can use change detection
+    if value != meter.value {
+        meter.value = value;
+    }
+
+    if !meter.collapsed && n_blocks >= MIN_BLOCKS && value > COLLAPSE_THRESHOLD {
+        meter.collapsed = true;
+        collapse_events.send(Collapse);
+    } else if meter.collapsed && value < REARM_THRESHOLD {
+        meter.collapsed = false;
+    }
+}
+
+// system
+fn collapse_feedback(
+    mut collapse_events: EventReader<Collapse>,
+    mut camera_shake: ResMut<CameraShake>,
+    mut toasts: EventWriter<Toast>,
+) {
+    if collapse_events.iter().count() > 0 {
+        camera_shake.0 = 1.0;
+        toasts.send(Toast::new(ToastIcon::Info, "COLLAPSE!"));
+    }
+}
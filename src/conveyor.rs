@@ -0,0 +1,162 @@
+//
+// Conveyor floor modifier
+//
+// With `--conveyor <speed>`, the floor acts like a conveyor belt running at
+// `speed` blocks per second (negative runs left). A thin sensor along the top
+// of the floor finds the blocks on it, and their horizontal velocity is nudged
+// towards the belt speed every tick. Blocks higher up are dragged along by
+// friction, so the stack drifts sideways and piles up against a wall, or off
+// the edge of the board if it has no walls. The current tetromino rides along
+// too, and locks once it comes to rest against something.
+//
+// Like in magnets.rs, sleeping blocks are left alone. Waking them would keep
+// the stack, and any tetromino resting on it, from ever falling asleep, so no
+// tetromino would lock and the next one would never spawn.
+//
+// The belt speed is one of the modifiers in modifiers.rs, so it's part of share
+// codes, and replays recorded with it can't be played back without it.
+//
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::modifiers::Modifiers;
+use crate::{Block, Game, GameState};
+
+/// In terms of block size
+const BELT_HEIGHT: f32 = 0.2;
+
+/// How much of the difference to the belt speed is made up per tick
+const GRIP: f32 = 0.05;
+
+/// Resource: Belt speed, and the blocks on the belt
+struct Conveyor {
+    speed: f32,
+    on_belt: HashSet<Entity>,
+}
+
+struct ConveyorBelt;
+
+pub struct ConveyorPlugin;
+
+impl Plugin for ConveyorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let speed = match Modifiers::of_app(app).conveyor {
+            Some(speed) => speed,
+            None => return,
+        };
+
+        app.insert_resource(Conveyor {
+            speed,
+            on_belt: HashSet::new(),
+        })
+        .add_startup_system(setup_conveyor_belt.system())
+        .add_system(conveyor_belt_intersections.system())
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing).with_system(run_conveyor_belt.system()),
+        );
+    }
+}
+
+// startup system
+fn setup_conveyor_belt(mut commands: Commands, game: Res<Game>) {
+    commands
+        .spawn()
+        .insert_bundle(ColliderBundle {
+            collider_type: ColliderType::Sensor,
+            shape: ColliderShape::cuboid(game.n_lanes as f32 * 0.5, BELT_HEIGHT * 0.5),
+            position: [0.0, game.floor_y() + BELT_HEIGHT * 0.5].into(),
+            flags: ColliderFlags {
+                active_events: ActiveEvents::INTERSECTION_EVENTS,
+                ..ColliderFlags::default()
+            },
+            ..ColliderBundle::default()
+        })
+        .insert(ConveyorBelt);
+}
+
+// system
+fn conveyor_belt_intersections(
+    mut intersection_events: EventReader<IntersectionEvent>,
+    mut conveyor: ResMut<Conveyor>,
+    belt_query: Query<Entity, With<ConveyorBelt>>,
+) {
+    for event in intersection_events.iter() {
+        let entity1 = event.collider1.entity();
+        let entity2 = event.collider2.entity();
+
+        let block_entity = if belt_query.get(entity1).is_ok() {
+            entity2
+        } else if belt_query.get(entity2).is_ok() {
+            entity1
+        } else {
+            continue;
+        };
+
+        if event.intersecting {
+            conveyor.on_belt.insert(block_entity);
+        } else {
+            conveyor.on_belt.remove(&block_entity);
+        }
+    }
+}
+
+// system
+fn run_conveyor_belt(
+    mut conveyor: ResMut<Conveyor>,
+    mut block_query: Query<(&mut RigidBodyVelocity, &RigidBodyActivation), With<Block>>,
+) {
+    let conveyor = &mut *conveyor;
+
+    // Despawned blocks don't always get an intersection stop event
+    conveyor
+        .on_belt
+        .retain(|block_entity| block_query.get_mut(*block_entity).is_ok());
+
+    for block_entity in conveyor.on_belt.iter() {
+        if let Ok((mut velocity, activation)) = block_query.get_mut(*block_entity) {
+            if let Some(x) = belt_velocity(conveyor.speed, velocity.linvel.x, activation.sleeping) {
+                velocity.linvel.x = x;
+            }
+        }
+    }
+}
+
+///
+/// The horizontal velocity of a block on the belt after one tick, or None if
+/// it's asleep and should stay that way
+///
+fn belt_velocity(speed: f32, x: f32, sleeping: bool) -> Option<f32> {
+    if sleeping {
+        None
+    } else {
+        Some(x + (speed - x) * GRIP)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resting_blocks_stay_asleep() {
+        // A tetromino can only lock once all of its blocks are asleep
+        assert_eq!(belt_velocity(1.0, 0.0, true), None);
+        assert_eq!(belt_velocity(-2.0, 0.5, true), None);
+    }
+
+    #[test]
+    fn moving_blocks_are_pulled_to_belt_speed() {
+        let mut x = 0.0;
+        for _ in 0..200 {
+            x = belt_velocity(1.5, x, false).unwrap();
+        }
+        assert!((x - 1.5).abs() < 0.01);
+
+        // Faster than the belt, e.g. pushed by the player, slows down
+        let slower = belt_velocity(1.5, 3.0, false).unwrap();
+        assert!(slower < 3.0 && slower > 1.5);
+    }
+}
@@ -0,0 +1,160 @@
+//
+// Damping zones along the sides of the board
+//
+// A piece pushed against the side of the board with `Tuning::movement_force` tends to
+// jitter. Thin sensors against the faces of both walls find the blocks touching
+// them, and only the part of their velocity heading into the wall is damped.
+// That calms the jitter without slowing down anything else: pieces still fall,
+// and move away from the wall, as fast as anywhere else on the board.
+//
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::dynamics::IntegrationParameters;
+
+use crate::{Block, Game, GameState};
+
+/// In terms of block size. Thin enough that only blocks touching the wall are in it.
+const ZONE_WIDTH: f32 = 0.1;
+
+/// Like Rapier's linear damping, but only towards the wall
+const ZONE_DAMPING: f32 = 8.0;
+
+/// Which wall the zone is against: -1 for left, 1 for right
+struct DampingZone(f32);
+
+/// Resource: The blocks in a damping zone, and the side of its wall
+#[derive(Default)]
+struct InDampingZone(HashMap<Entity, f32>);
+
+pub struct DampingZonePlugin;
+
+impl Plugin for DampingZonePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<InDampingZone>()
+            .add_startup_system(setup_damping_zones.system())
+            .add_system(damping_zone_intersections.system())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(damp_towards_walls.system()),
+            );
+    }
+}
+
+// startup system
+fn setup_damping_zones(mut commands: Commands, game: Res<Game>) {
+    // The wall faces are at the edges of the board
+    let zone_x = game.left_edge_x() + ZONE_WIDTH * 0.5;
+
+    for (x, side) in [(zone_x, -1.0), (-zone_x, 1.0)].iter() {
+        commands
+            .spawn()
+            .insert_bundle(ColliderBundle {
+                collider_type: ColliderType::Sensor,
+                shape: ColliderShape::cuboid(ZONE_WIDTH * 0.5, game.n_rows as f32 * 0.5),
+                position: [*x, 0.0].into(),
+                flags: ColliderFlags {
+                    active_events: ActiveEvents::INTERSECTION_EVENTS,
+                    ..ColliderFlags::default()
+                },
+                ..ColliderBundle::default()
+            })
+            .insert(DampingZone(*side));
+    }
+}
+
+// system
+fn damping_zone_intersections(
+    mut intersection_events: EventReader<IntersectionEvent>,
+    mut in_zone: ResMut<InDampingZone>,
+    zone_query: Query<&DampingZone>,
+    block_query: Query<(), With<Block>>,
+) {
+    for event in intersection_events.iter() {
+        let entity1 = event.collider1.entity();
+        let entity2 = event.collider2.entity();
+
+        let (zone, block_entity) = if let Ok(zone) = zone_query.get(entity1) {
+            (zone, entity2)
+        } else if let Ok(zone) = zone_query.get(entity2) {
+            (zone, entity1)
+        } else {
+            continue;
+        };
+
+        if block_query.get(block_entity).is_err() {
+            continue;
+        }
+
+        if event.intersecting {
+            in_zone.0.insert(block_entity, zone.0);
+        } else {
+            in_zone.0.remove(&block_entity);
+        }
+    }
+}
+
+// system
+fn damp_towards_walls(
+    integration_parameters: Res<IntegrationParameters>,
+    mut in_zone: ResMut<InDampingZone>,
+    mut velocity_query: Query<(&mut RigidBodyVelocity, &RigidBodyActivation), With<Block>>,
+) {
+    // Despawned blocks don't always get an intersection stop event
+    in_zone
+        .0
+        .retain(|block_entity, _| velocity_query.get_mut(*block_entity).is_ok());
+
+    for (block_entity, side) in in_zone.0.iter() {
+        if let Ok((mut velocity, activation)) = velocity_query.get_mut(*block_entity) {
+            if let Some(x) = damped_velocity(
+                velocity.linvel.x,
+                *side,
+                activation.sleeping,
+                integration_parameters.dt,
+            ) {
+                velocity.linvel.x = x;
+            }
+        }
+    }
+}
+
+///
+/// The horizontal velocity of a block against the wall on `side` after one
+/// tick, or None if it's left alone: asleep, or not heading into the wall
+///
+fn damped_velocity(x: f32, side: f32, sleeping: bool, dt: f32) -> Option<f32> {
+    if sleeping || x * side <= 0.0 {
+        None
+    } else {
+        Some(x / (1.0 + ZONE_DAMPING * dt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    #[test]
+    fn damps_towards_the_wall() {
+        let left = damped_velocity(-2.0, -1.0, false, DT).unwrap();
+        assert!(left > -2.0 && left < 0.0);
+
+        let right = damped_velocity(2.0, 1.0, false, DT).unwrap();
+        assert!(right < 2.0 && right > 0.0);
+    }
+
+    #[test]
+    fn leaves_other_motion_alone() {
+        // Moving away from the wall
+        assert_eq!(damped_velocity(2.0, -1.0, false, DT), None);
+        assert_eq!(damped_velocity(-2.0, 1.0, false, DT), None);
+        // Only falling
+        assert_eq!(damped_velocity(0.0, -1.0, false, DT), None);
+        // Asleep
+        assert_eq!(damped_velocity(-2.0, -1.0, true, DT), None);
+    }
+}
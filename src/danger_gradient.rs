@@ -0,0 +1,92 @@
+//
+// Danger gradient
+//
+// The board background is tinted from the floor up to the top of the stack,
+// going from green at the bottom of the board to red at the top. The taller the
+// stack, the further the tint reaches into red, so the danger can be seen
+// without reading anything.
+//
+// The stack height comes from `BoardGrid::column_heights`, see board_grid.rs.
+//
+
+use bevy::prelude::*;
+
+use crate::board_grid::BoardGrid;
+use crate::{Game, Tuning};
+
+/// Behind the blocks, but in front of the 2D camera's far plane
+const BACKGROUND_Z: f32 = -0.05;
+
+const TINT_ALPHA: f32 = 0.25;
+
+/// One background sprite per board row
+struct DangerRow {
+    row: usize,
+}
+
+pub struct DangerGradientPlugin;
+
+impl Plugin for DangerGradientPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup_danger_rows.system())
+            .add_system(tint_danger_rows.system());
+    }
+}
+
+// startup system
+fn setup_danger_rows(
+    mut commands: Commands,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for row in 0..game.n_rows {
+        let (_, y) = game.board_to_physics((0, row as i32));
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: materials.add(Color::NONE.into()),
+                sprite: Sprite::new(Vec2::new(
+                    game.n_lanes as f32 * tuning.block_px_size,
+                    tuning.block_px_size,
+                )),
+                transform: Transform::from_xyz(0.0, y * tuning.block_px_size, BACKGROUND_Z),
+                ..Default::default()
+            })
+            .insert(DangerRow { row });
+    }
+}
+
+// system
+fn tint_danger_rows(
+    game: Res<Game>,
+    grid: Res<BoardGrid>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    row_query: Query<(&DangerRow, &Handle<ColorMaterial>)>,
+) {
+    if !grid.is_changed() {
+        return;
+    }
+
+    let stack_height = grid.column_heights().into_iter().max().unwrap_or(0);
+
+    for (danger_row, material) in row_query.iter() {
+        if let Some(material) = materials.get_mut(material) {
+            material.color = if danger_row.row < stack_height {
+                danger_color(danger_row.row as f32 / game.n_rows as f32)
+            } else {
+                Color::NONE
+            };
+        }
+    }
+}
+
+///
+/// Green at 0 (the floor), yellow halfway and red at 1 (the top of the board)
+///
+fn danger_color(height: f32) -> Color {
+    let red = (height * 2.0).min(1.0);
+    let green = (2.0 - height * 2.0).min(1.0);
+
+    Color::rgba(red, green, 0.0, TINT_ALPHA)
+}
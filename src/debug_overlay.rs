@@ -0,0 +1,96 @@
+//
+// Debug overlay, enabled with `--features debug-overlay`
+//
+// Shows the internals of the piece randomizer and the shape of the stack, to
+// check that they behave.
+// F3 toggles it.
+//
+
+use bevy::prelude::*;
+
+use crate::board_grid::BoardGrid;
+use crate::collapse::ChaosMeter;
+use crate::{Game, TetrominoKind, FONT_PATH};
+
+struct DebugOverlayText;
+
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup_debug_overlay.system())
+            .add_system(toggle_debug_overlay.system())
+            .add_system(update_debug_overlay.system());
+    }
+}
+
+// startup system
+fn setup_debug_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(FONT_PATH),
+                    font_size: 16.0,
+                    color: Color::rgb(1.0, 1.0, 0.0),
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(DebugOverlayText);
+}
+
+// system
+fn toggle_debug_overlay(
+    input: Res<Input<KeyCode>>,
+    mut overlay_query: Query<&mut Visible, With<DebugOverlayText>>,
+) {
+    if input.just_pressed(KeyCode::F3) {
+        for mut visible in overlay_query.iter_mut() {
+            visible.is_visible = !visible.is_visible;
+        }
+    }
+}
+
+// system
+fn update_debug_overlay(
+    game: Res<Game>,
+    grid: Res<BoardGrid>,
+    chaos: Res<ChaosMeter>,
+    mut overlay_query: Query<&mut Text, With<DebugOverlayText>>,
+) {
+    if !game.is_changed() && !grid.is_changed() && !chaos.is_changed() {
+        return;
+    }
+
+    for mut text in overlay_query.iter_mut() {
+        text.sections[0].value = format!(
+            "seed: {}\nbag: {}\nupcoming: {}\nheights: {:?}\nholes: {}, bumpiness: {}\nchaos: {:.2}",
+            game.seed,
+            kinds_to_string(game.pieces.remaining_in_bag()),
+            kinds_to_string(game.pieces.upcoming()),
+            grid.column_heights(),
+            grid.holes(),
+            grid.bumpiness(),
+            chaos.value,
+        );
+    }
+}
+
+fn kinds_to_string(kinds: impl Iterator<Item = TetrominoKind>) -> String {
+    kinds
+        .map(|kind| format!("{:?}", kind))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,130 @@
+//
+// Entity budget
+//
+// Counts entities, rigid bodies, colliders and joints every frame and reports
+// them as Bevy diagnostics. Each count has a budget derived from the board
+// size; going over it prints a warning, which is how leaks show up (like
+// joints that are never despawned).
+//
+// `--diagnostics` also logs the counts, with the frame time, every second.
+//
+
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticId, Diagnostics, FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin,
+};
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::Game;
+
+pub const ENTITY_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(0x8f0c_6e1a_5a34_4b7e_9d2f_1c3b_7a60_0001);
+pub const BODY_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(0x8f0c_6e1a_5a34_4b7e_9d2f_1c3b_7a60_0002);
+pub const COLLIDER_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(0x8f0c_6e1a_5a34_4b7e_9d2f_1c3b_7a60_0003);
+pub const JOINT_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(0x8f0c_6e1a_5a34_4b7e_9d2f_1c3b_7a60_0004);
+
+/// Entities that aren't blocks: camera, floor, sensors, UI
+const ENTITY_OVERHEAD: usize = 200;
+
+/// Bodies and colliders that aren't blocks: floor, walls, sensors.
+/// The floor can also be split into a segment per lane, see floor_gaps.rs.
+const BODY_OVERHEAD: usize = 10;
+
+/// Joints of the current tetromino, and the one before it while it's replaced
+const JOINT_BUDGET: usize = 8;
+
+/// Resource: Which budgets are currently exceeded, so each warning prints once
+#[derive(Default)]
+struct OverBudget {
+    entities: bool,
+    bodies: bool,
+    colliders: bool,
+    joints: bool,
+}
+
+pub struct EntityBudgetPlugin;
+
+impl Plugin for EntityBudgetPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if crate::has_flag("--diagnostics") {
+            app.add_plugin(FrameTimeDiagnosticsPlugin::default())
+                .add_plugin(LogDiagnosticsPlugin::default());
+        }
+
+        app.init_resource::<OverBudget>()
+            .add_startup_system(setup_diagnostics.system())
+            .add_system(count_entities.system());
+    }
+}
+
+// startup system
+fn setup_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(ENTITY_COUNT, "entity_count", 20));
+    diagnostics.add(Diagnostic::new(BODY_COUNT, "body_count", 20));
+    diagnostics.add(Diagnostic::new(COLLIDER_COUNT, "collider_count", 20));
+    diagnostics.add(Diagnostic::new(JOINT_COUNT, "joint_count", 20));
+}
+
+// system
+fn count_entities(
+    game: Res<Game>,
+    mut diagnostics: ResMut<Diagnostics>,
+    mut over_budget: ResMut<OverBudget>,
+    entity_query: Query<Entity>,
+    body_query: Query<&RigidBodyType>,
+    collider_query: Query<&ColliderType>,
+    joint_query: Query<&JointHandleComponent>,
+) {
+    let over_budget = &mut *over_budget;
+
+    // Every cell of the board filled, plus a tetromino on top
+    let max_blocks = game.n_lanes * game.n_rows + 4;
+
+    let counts = [
+        (
+            ENTITY_COUNT,
+            entity_query.iter().count(),
+            // Plus a background sprite per row and the current tetromino's outlines
+            max_blocks + game.n_rows + 4 + ENTITY_OVERHEAD,
+            &mut over_budget.entities,
+            "entities",
+        ),
+        (
+            BODY_COUNT,
+            body_query.iter().count(),
+            max_blocks + game.n_lanes + BODY_OVERHEAD,
+            &mut over_budget.bodies,
+            "rigid bodies",
+        ),
+        (
+            COLLIDER_COUNT,
+            collider_query.iter().count(),
+            max_blocks + game.n_lanes + BODY_OVERHEAD,
+            &mut over_budget.colliders,
+            "colliders",
+        ),
+        (
+            JOINT_COUNT,
+            joint_query.iter().count(),
+            JOINT_BUDGET,
+            &mut over_budget.joints,
+            "joints",
+        ),
+    ];
+
+    for (id, count, budget, was_over, name) in counts {
+        diagnostics.add_measurement(id, count as f64);
+
+        let is_over = count > budget;
+        if is_over && !*was_over {
+            println!(
+                "Warning: {} {} exist, expected at most {}. Is something not despawned?",
+                count, name, budget
+            );
+        }
+        *was_over = is_over;
+    }
+}
@@ -0,0 +1,210 @@
+//
+// Gameplay event log
+//
+// `--event-log <file>` writes one JSON object per line for everything that
+// happens in a game, for analysing play with external tools:
+//
+//   {"tick":0,"event":"start","seed":123,"lanes":10,"rows":20}
+//   {"tick":0,"event":"spawn","piece":"T","number":1}
+//   {"tick":42,"event":"move","direction":-1}
+//   {"tick":50,"event":"rotate","direction":1}
+//   {"tick":97,"event":"lost","block":"12v0"}
+//   {"tick":160,"event":"lock","piece":"T","number":1,"x":0.48,"y":-8.02,"angle":90.3}
+//   {"tick":160,"event":"clear","lines":1}
+//
+// Ticks count frames of play, like in replays, so paused time doesn't count.
+// A restart logs a new "start" and the ticks start over from 0.
+//
+// Directions are -1, 0 or 1: left or right for "move", and clockwise or
+// counterclockwise for "rotate", logged whenever they change. "lock" has the
+// pose the piece came to rest in: the center of its blocks in physics
+// coordinates, and the angle of its first block in degrees. "clear" is only
+// logged for locks that cleared lines.
+//
+
+use std::fs::File;
+use std::io::{LineWriter, Write};
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::line_clearing::LinesCleared;
+use crate::out_of_play::BlockLost;
+use crate::{Block, Game, GameState, PieceLocked, Restart, LINE_CLEARING, RESTART};
+
+/// Resource: present when logging
+struct EventLog {
+    writer: LineWriter<File>,
+    tick: u64,
+}
+
+impl EventLog {
+    fn write(&mut self, event: &str, fields: &str) {
+        let line = if fields.is_empty() {
+            format!("{{\"tick\":{},\"event\":\"{}\"}}\n", self.tick, event)
+        } else {
+            format!(
+                "{{\"tick\":{},\"event\":\"{}\",{}}}\n",
+                self.tick, event, fields
+            )
+        };
+
+        if let Err(err) = self.writer.write_all(line.as_bytes()) {
+            println!("Failed to write event log: {}", err);
+        }
+    }
+
+    fn write_start(&mut self, game: &Game) {
+        self.write(
+            "start",
+            &format!(
+                "\"seed\":{},\"lanes\":{},\"rows\":{}",
+                game.seed, game.n_lanes, game.n_rows
+            ),
+        );
+    }
+}
+
+pub struct EventLogPlugin;
+
+impl Plugin for EventLogPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if let Some(path) = crate::arg_value("--event-log") {
+            match File::create(&path) {
+                Ok(file) => {
+                    app.insert_resource(EventLog {
+                        writer: LineWriter::new(file),
+                        tick: 0,
+                    })
+                    .add_startup_system(log_start.system())
+                    .add_system(log_events.system().after(RESTART).after(LINE_CLEARING));
+                }
+                Err(err) => println!("Can't create event log {}: {}", path, err),
+            }
+        }
+    }
+}
+
+// startup system
+fn log_start(mut log: ResMut<EventLog>, game: Res<Game>) {
+    log.write_start(&game);
+}
+
+///
+/// Checks the state itself, since it also needs to see restarts chosen from
+/// the pause menu.
+///
+// system
+fn log_events(
+    mut log: ResMut<EventLog>,
+    state: Res<State<GameState>>,
+    input: Res<Input<KeyCode>>,
+    game: Res<Game>,
+    mut restart_events: EventReader<Restart>,
+    mut lost_events: EventReader<BlockLost>,
+    mut locked_events: EventReader<PieceLocked>,
+    mut cleared_events: EventReader<LinesCleared>,
+    block_query: Query<(&Block, &RigidBodyPosition)>,
+    mut n_pieces_logged: Local<usize>,
+    mut last_movement: Local<i8>,
+    mut last_rotation: Local<i8>,
+) {
+    if restart_events.iter().count() > 0 {
+        log.tick = 0;
+        *n_pieces_logged = 0;
+        log.write_start(&game);
+    }
+
+    if game.n_pieces > *n_pieces_logged {
+        *n_pieces_logged = game.n_pieces;
+        let piece = game
+            .current_tetromino_kind
+            .map(|kind| format!("{:?}", kind))
+            .unwrap_or_default();
+        log.write(
+            "spawn",
+            &format!("\"piece\":\"{}\",\"number\":{}", piece, game.n_pieces),
+        );
+    }
+
+    for BlockLost(block_entity) in lost_events.iter() {
+        log.write("lost", &format!("\"block\":\"{:?}\"", block_entity));
+    }
+
+    for PieceLocked { blocks } in locked_events.iter() {
+        log_lock(&mut log, blocks, &block_query);
+    }
+
+    for LinesCleared(lines) in cleared_events.iter() {
+        if *lines > 0 {
+            log.write("clear", &format!("\"lines\":{}", lines));
+        }
+    }
+
+    if *state.current() != GameState::Playing {
+        return;
+    }
+
+    let movement = input.pressed(KeyCode::Right) as i8 - input.pressed(KeyCode::Left) as i8;
+    if movement != *last_movement {
+        *last_movement = movement;
+        log.write("move", &format!("\"direction\":{}", movement));
+    }
+
+    let counterclockwise = input.pressed(KeyCode::Up) || input.pressed(KeyCode::Z);
+    let rotation = counterclockwise as i8 - input.pressed(KeyCode::X) as i8;
+    if rotation != *last_rotation {
+        *last_rotation = rotation;
+        log.write("rotate", &format!("\"direction\":{}", rotation));
+    }
+
+    log.tick += 1;
+}
+
+///
+/// Blocks that were lost after the piece locked are left out of its pose
+///
+fn log_lock(
+    log: &mut EventLog,
+    blocks: &[Entity],
+    block_query: &Query<(&Block, &RigidBodyPosition)>,
+) {
+    let locked: Vec<_> = blocks
+        .iter()
+        .filter_map(|block_entity| block_query.get(*block_entity).ok())
+        .collect();
+    let (first_block, first_position) = match locked.first() {
+        Some(first) => *first,
+        // Every block was lost on the way down
+        None => return,
+    };
+
+    let n_blocks = locked.len() as f32;
+    let x = locked
+        .iter()
+        .map(|(_, position)| position.position.translation.x)
+        .sum::<f32>()
+        / n_blocks;
+    let y = locked
+        .iter()
+        .map(|(_, position)| position.position.translation.y)
+        .sum::<f32>()
+        / n_blocks;
+    let angle = first_position.position.rotation.angle().to_degrees();
+
+    let piece = first_block
+        .kind
+        .map(|kind| format!("{:?}", kind))
+        .unwrap_or_default();
+    log.write(
+        "lock",
+        &format!(
+            "\"piece\":\"{}\",\"number\":{},\"x\":{:.2},\"y\":{:.2},\"angle\":{:.1}",
+            piece,
+            first_block.piece.unwrap_or_default(),
+            x,
+            y,
+            angle
+        ),
+    );
+}
@@ -0,0 +1,178 @@
+//
+// Floor gaps hazard
+//
+// With `--floor-gaps`, the floor is split into one segment per lane, and every
+// `GAP_INTERVAL` seconds one of them opens for `GAP_OPEN` seconds: its collider
+// turns into a sensor and its sprite shrinks away. Blocks that aren't held up
+// by their neighbours fall through, and are caught by the out-of-play sensors
+// below the board (see out_of_play.rs).
+//
+// Which lane opens follows a fixed pattern from the game's seed, and time is
+// counted in physics steps, so replays of the same game play out the same. The
+// flag is one of the modifiers in modifiers.rs, so it's part of share codes,
+// and replays recorded with it can't be played back without it.
+//
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::dynamics::IntegrationParameters;
+
+use crate::modifiers::Modifiers;
+use crate::{Floor, Game, GameState, Restart, Tuning};
+
+// In seconds
+const GAP_INTERVAL: f32 = 20.0;
+const GAP_OPEN: f32 = 3.0;
+const SHRINK: f32 = 0.3;
+
+/// One lane's worth of the floor
+struct FloorSegment {
+    lane: usize,
+}
+
+/// Resource: Present when the hazard is on
+struct FloorGaps {
+    /// Since the last gap closed, or since the game started
+    elapsed: f32,
+    /// The lane that is open, and for how long it has been
+    open: Option<(usize, f32)>,
+    n_gaps: usize,
+}
+
+impl FloorGaps {
+    fn new() -> Self {
+        Self {
+            elapsed: 0.0,
+            open: None,
+            n_gaps: 0,
+        }
+    }
+}
+
+pub struct FloorGapsPlugin;
+
+impl Plugin for FloorGapsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if !Modifiers::of_app(app).floor_gaps {
+            return;
+        }
+
+        app.insert_resource(FloorGaps::new())
+            // The floor is spawned by setup_game
+            .add_startup_system_to_stage(StartupStage::PostStartup, split_floor.system())
+            .add_system(reset_floor_gaps.system())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(open_floor_gaps.system()),
+            );
+    }
+}
+
+// startup system
+fn split_floor(
+    mut commands: Commands,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    floor_query: Query<Entity, With<Floor>>,
+) {
+    for floor in floor_query.iter() {
+        commands.entity(floor).despawn();
+    }
+
+    let floor_block_height = tuning.floor_block_height;
+    let y = game.floor_y() - floor_block_height * 0.5;
+    let material = materials.add(Color::rgb(0.5, 0.5, 0.5).into());
+
+    for lane in 0..game.n_lanes {
+        let (x, _) = game.board_to_physics((lane as i32, 0));
+
+        commands
+            .spawn()
+            .insert_bundle(SpriteBundle {
+                material: material.clone(),
+                sprite: Sprite::new(Vec2::new(
+                    tuning.block_px_size,
+                    floor_block_height * tuning.block_px_size,
+                )),
+                transform: Transform::from_xyz(
+                    x * tuning.block_px_size,
+                    y * tuning.block_px_size,
+                    0.0,
+                ),
+                ..Default::default()
+            })
+            .insert_bundle(RigidBodyBundle {
+                body_type: RigidBodyType::Static,
+                position: [x, y].into(),
+                ..RigidBodyBundle::default()
+            })
+            .insert_bundle(ColliderBundle {
+                shape: ColliderShape::cuboid(0.5, floor_block_height * 0.5),
+                material: ColliderMaterial {
+                    friction: tuning.floor_friction,
+                    ..ColliderMaterial::default()
+                },
+                ..ColliderBundle::default()
+            })
+            .insert(Floor)
+            .insert(FloorSegment { lane });
+    }
+}
+
+// system
+fn reset_floor_gaps(mut restart_events: EventReader<Restart>, mut gaps: ResMut<FloorGaps>) {
+    if restart_events.iter().count() > 0 {
+        *gaps = FloorGaps::new();
+    }
+}
+
+// system
+fn open_floor_gaps(
+    game: Res<Game>,
+    integration_parameters: Res<IntegrationParameters>,
+    mut gaps: ResMut<FloorGaps>,
+    mut segment_query: Query<(&FloorSegment, &mut ColliderType, &mut Transform)>,
+) {
+    let dt = integration_parameters.dt;
+    let gaps = &mut *gaps;
+
+    gaps.open = match gaps.open {
+        None => {
+            gaps.elapsed += dt;
+            if gaps.elapsed < GAP_INTERVAL {
+                None
+            } else {
+                // Scattered around the board, the same way in every replay of the game
+                let hash = (game.seed.wrapping_add(gaps.n_gaps as u64))
+                    .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+                    >> 32;
+                let lane = hash as usize % game.n_lanes;
+                gaps.n_gaps += 1;
+                Some((lane, 0.0))
+            }
+        }
+        Some((lane, open_for)) if open_for + dt < GAP_OPEN => Some((lane, open_for + dt)),
+        Some(_) => {
+            gaps.elapsed = 0.0;
+            None
+        }
+    };
+
+    for (segment, mut collider_type, mut transform) in segment_query.iter_mut() {
+        let (wanted_type, scale) = match gaps.open {
+            Some((lane, open_for)) if lane == segment.lane => {
+                let closing_in = GAP_OPEN - open_for;
+                let scale = 1.0 - (open_for.min(closing_in) / SHRINK).min(1.0);
+                (ColliderType::Sensor, scale)
+            }
+            _ => (ColliderType::Solid, 1.0),
+        };
+
+        if *collider_type != wanted_type {
+            *collider_type = wanted_type;
+        }
+        if transform.scale.x != scale {
+            transform.scale.x = scale;
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//
+// Frame pacing
+//
+//  * `--no-vsync` turns vsync off
+//  * `--fps-cap <fps>` limits the frame rate
+//
+// While paused or in a menu, the frame rate drops to `IDLE_FPS` to save
+// battery. Physics is already switched off then, see pause.rs.
+//
+// Rapier is stepped once per frame with a fixed timestep, so the game runs at
+// real time speed only at 60 frames per second. Without vsync, `--fps-cap 60`
+// keeps it there.
+//
+// The cap waits at the very end of the frame, after rendering. Input is then
+// read right before the next frame's logic runs, rather than going stale
+// during the wait, which keeps input latency as low as it gets.
+//
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+use crate::GameState;
+
+const IDLE_FPS: f64 = 10.0;
+
+/// Resource: Shortest time between frames while playing, if capped
+struct FrameCap(Option<Duration>);
+
+///
+/// Must be added before the window plugin, which reads `WindowDescriptor`.
+///
+pub struct FramePacingPlugin;
+
+impl Plugin for FramePacingPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(WindowDescriptor {
+            vsync: !crate::has_flag("--no-vsync"),
+            ..Default::default()
+        });
+
+        let fps_cap = crate::arg_value("--fps-cap")
+            .and_then(|fps| fps.parse::<f64>().ok())
+            .filter(|fps| *fps > 0.0);
+
+        app.insert_resource(FrameCap(
+            fps_cap.map(|fps| Duration::from_secs_f64(1.0 / fps)),
+        ))
+        .add_system_to_stage(CoreStage::Last, cap_frame_rate.system());
+    }
+}
+
+// system
+fn cap_frame_rate(
+    cap: Res<FrameCap>,
+    state: Res<State<GameState>>,
+    mut last_frame_end: Local<Option<Instant>>,
+) {
+    let frame_time = match *state.current() {
+        GameState::Playing => cap.0,
+        GameState::Menu | GameState::Paused | GameState::GameOver => {
+            cap.0.max(Some(Duration::from_secs_f64(1.0 / IDLE_FPS)))
+        }
+    };
+
+    if let (Some(frame_time), Some(last_frame_end)) = (frame_time, *last_frame_end) {
+        let elapsed = last_frame_end.elapsed();
+        if elapsed < frame_time {
+            std::thread::sleep(frame_time - elapsed);
+        }
+    }
+
+    *last_frame_end = Some(Instant::now());
+}
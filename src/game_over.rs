@@ -0,0 +1,160 @@
+//
+// Game over
+//
+// Whatever ends the game calls `game_over` with the reason, which switches to
+// `GameState::GameOver`. Physics stops, and a screen shows the reason, the
+// time played and the score, with the choice to restart or quit. There's no
+// resuming a game that's over.
+//
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::game_timer::GameTimer;
+use crate::menu::{spawn_menu_screen, MenuChosen, MenuFocus, MenuMaterials};
+use crate::score::Score;
+use crate::{GameState, Restart, FONT_PATH};
+
+/// Resource: Why the game is over, shown on the game over screen
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GameOverReason {
+    /// The stack reached the rows new tetrominos spawn in
+    ToppedOut,
+    /// See shrinking_board.rs
+    BoardTooNarrow,
+}
+
+impl GameOverReason {
+    fn message(&self) -> &'static str {
+        match self {
+            Self::ToppedOut => "The stack reached the top",
+            Self::BoardTooNarrow => "The board is too narrow to go on",
+        }
+    }
+}
+
+/// Root UI node of the game over screen
+struct GameOverScreen;
+
+#[derive(Clone, Copy)]
+enum GameOverMenuItem {
+    Restart,
+    Quit,
+}
+
+impl GameOverMenuItem {
+    const ALL: [Self; 2] = [Self::Restart, Self::Quit];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Restart => "Restart",
+            Self::Quit => "Quit",
+        }
+    }
+}
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(GameOverReason::ToppedOut)
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(show_game_over_screen.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::GameOver)
+                    .with_system(game_over_menu_actions.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::GameOver).with_system(hide_game_over_screen.system()),
+            );
+    }
+}
+
+///
+/// End the game for the given reason, unless it's not being played
+///
+pub fn game_over(
+    state: &mut State<GameState>,
+    game_over_reason: &mut GameOverReason,
+    reason: GameOverReason,
+) {
+    if *state.current() == GameState::Playing && state.set(GameState::GameOver).is_ok() {
+        *game_over_reason = reason;
+    }
+}
+
+// system
+fn show_game_over_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    menu_materials: Res<MenuMaterials>,
+    mut focus: ResMut<MenuFocus>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    reason: Res<GameOverReason>,
+    game_timer: Res<GameTimer>,
+    score: Res<Score>,
+) {
+    rapier_config.physics_pipeline_active = false;
+    focus.0 = 0;
+
+    let subtitle = format!(
+        "{}\nTime: {:.1} seconds\nScore: {}",
+        reason.message(),
+        game_timer.seconds,
+        score.points
+    );
+    let labels: Vec<&str> = GameOverMenuItem::ALL
+        .iter()
+        .map(|item| item.label())
+        .collect();
+
+    let screen = spawn_menu_screen(
+        &mut commands,
+        asset_server.load(FONT_PATH),
+        materials.add(Color::rgba(0.0, 0.0, 0.0, 0.7).into()),
+        &menu_materials,
+        "GAME OVER",
+        &subtitle,
+        &labels,
+    );
+    commands.entity(screen).insert(GameOverScreen);
+}
+
+// system
+fn game_over_menu_actions(
+    mut chosen_events: EventReader<MenuChosen>,
+    mut state: ResMut<State<GameState>>,
+    mut restart_events: EventWriter<Restart>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    let chosen = chosen_events
+        .iter()
+        .last()
+        .and_then(|MenuChosen(index)| GameOverMenuItem::ALL.get(*index).copied());
+
+    match chosen {
+        Some(GameOverMenuItem::Restart) => {
+            let _ = state.set(GameState::Playing);
+            restart_events.send(Restart);
+        }
+        Some(GameOverMenuItem::Quit) => app_exit_events.send(AppExit),
+        None => {}
+    }
+}
+
+// system
+fn hide_game_over_screen(
+    mut commands: Commands,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    screen_query: Query<Entity, With<GameOverScreen>>,
+) {
+    rapier_config.physics_pipeline_active = true;
+
+    for screen in screen_query.iter() {
+        commands.entity(screen).despawn_recursive();
+    }
+}
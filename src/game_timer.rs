@@ -0,0 +1,128 @@
+//
+// Game timer
+//
+// Elapsed play time in the top left corner. It adds up the physics timestep
+// for every tick played, rather than reading the wall clock, so paused time
+// doesn't count and the time matches between a game and its replay.
+//
+// `--timer <precision>` picks what is shown: `tenths` (default), `ms`, or `off`.
+//
+
+use bevy::prelude::*;
+use bevy_rapier2d::rapier::dynamics::IntegrationParameters;
+
+use crate::{GameState, Restart, FONT_PATH};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TimerPrecision {
+    Off,
+    Tenths,
+    Milliseconds,
+}
+
+/// Resource: Res<GameTimer>
+#[derive(Default)]
+pub struct GameTimer {
+    pub seconds: f64,
+}
+
+struct GameTimerText;
+
+pub struct GameTimerPlugin;
+
+impl Plugin for GameTimerPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let precision = match crate::arg_value("--timer").as_deref() {
+            None | Some("tenths") => TimerPrecision::Tenths,
+            Some("ms") => TimerPrecision::Milliseconds,
+            Some("off") => TimerPrecision::Off,
+            Some(other) => {
+                println!("Unknown --timer {}, expected tenths, ms or off", other);
+                TimerPrecision::Tenths
+            }
+        };
+
+        app.insert_resource(precision)
+            .init_resource::<GameTimer>()
+            .add_startup_system(setup_game_timer.system())
+            .add_system(reset_game_timer.system())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(tick_game_timer.system()),
+            )
+            .add_system(show_game_timer.system());
+    }
+}
+
+// startup system
+fn setup_game_timer(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    precision: Res<TimerPrecision>,
+) {
+    if *precision == TimerPrecision::Off {
+        return;
+    }
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(FONT_PATH),
+                    font_size: 32.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(GameTimerText);
+}
+
+// system
+fn tick_game_timer(
+    integration_parameters: Res<IntegrationParameters>,
+    mut timer: ResMut<GameTimer>,
+) {
+    timer.seconds += integration_parameters.dt as f64;
+}
+
+// system
+fn reset_game_timer(mut restart_events: EventReader<Restart>, mut timer: ResMut<GameTimer>) {
+    if restart_events.iter().count() > 0 {
+        *timer = GameTimer::default();
+    }
+}
+
+// system
+fn show_game_timer(
+    timer: Res<GameTimer>,
+    precision: Res<TimerPrecision>,
+    mut text_query: Query<&mut Text, With<GameTimerText>>,
+) {
+    if !timer.is_changed() {
+        return;
+    }
+
+    // Truncate rather than round, so the display never runs ahead
+    let ms = (timer.seconds * 1000.0) as u64;
+    let (minutes, seconds) = (ms / 60_000, ms / 1000 % 60);
+    let value = match *precision {
+        TimerPrecision::Off => return,
+        TimerPrecision::Tenths => format!("{}:{:02}.{}", minutes, seconds, ms / 100 % 10),
+        TimerPrecision::Milliseconds => format!("{}:{:02}.{:03}", minutes, seconds, ms % 1000),
+    };
+
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
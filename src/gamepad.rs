@@ -0,0 +1,163 @@
+//
+// Gamepad play and hot-plugging
+//
+// During play, gamepad buttons press the keys in `GAMEPAD_KEYS`, the same way
+// script.rs does. So everything that reads the keyboard works with a gamepad
+// too: movement, replay recording and idle detection in pause.rs. Menus read
+// the gamepad themselves, see menu.rs. The keys held on the keyboard are
+// tracked separately, so letting go of a button doesn't release a key that is
+// still held on the keyboard.
+//
+// The gamepad that last pressed a button is the active one. If it's
+// disconnected mid-game, the game pauses and asks for it to be reconnected.
+// The next gamepad to connect, or to press a button, becomes the active one.
+//
+
+use std::collections::HashSet;
+
+use bevy::input::gamepad::{GamepadEvent, GamepadEventType};
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::{ElementState, InputSystem};
+use bevy::prelude::*;
+
+use crate::pause::{pause, PauseReason};
+use crate::toast::{Toast, ToastIcon};
+use crate::GameState;
+
+/// The key each gamepad button presses during play
+const GAMEPAD_KEYS: [(GamepadButtonType, KeyCode); 6] = [
+    (GamepadButtonType::DPadLeft, KeyCode::Left),
+    (GamepadButtonType::DPadRight, KeyCode::Right),
+    (GamepadButtonType::DPadDown, KeyCode::Down),
+    (GamepadButtonType::DPadUp, KeyCode::Up),
+    (GamepadButtonType::South, KeyCode::Z),
+    (GamepadButtonType::East, KeyCode::X),
+];
+
+/// Resource: The gamepad the player is using, if any
+#[derive(Default)]
+pub struct ActiveGamepad(pub Option<Gamepad>);
+
+/// Resource: The keys held down on the keyboard itself, whatever the gamepad does
+#[derive(Default)]
+struct HeldOnKeyboard(HashSet<KeyCode>);
+
+impl HeldOnKeyboard {
+    ///
+    /// Release a key the gamepad pressed, unless it's also held on the keyboard
+    ///
+    fn release(&self, input: &mut Input<KeyCode>, key: KeyCode) {
+        if !self.0.contains(&key) {
+            input.release(key);
+        }
+    }
+}
+
+pub struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ActiveGamepad>()
+            .init_resource::<HeldOnKeyboard>()
+            .add_system(gamepad_connections.system())
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                gamepad_keys.system().after(InputSystem),
+            );
+    }
+}
+
+// system
+fn gamepad_connections(
+    mut gamepad_events: EventReader<GamepadEvent>,
+    mut active_gamepad: ResMut<ActiveGamepad>,
+    held_on_keyboard: Res<HeldOnKeyboard>,
+    mut input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+    mut pause_reason: ResMut<PauseReason>,
+    mut toasts: EventWriter<Toast>,
+) {
+    for GamepadEvent(gamepad, event_type) in gamepad_events.iter() {
+        match event_type {
+            GamepadEventType::Connected if active_gamepad.0.is_none() => {
+                active_gamepad.0 = Some(*gamepad);
+                toasts.send(Toast::new(ToastIcon::Info, "Controller connected"));
+            }
+            GamepadEventType::Disconnected if active_gamepad.0 == Some(*gamepad) => {
+                active_gamepad.0 = None;
+                // The pad can't release what it was holding any more
+                for (_, key) in GAMEPAD_KEYS.iter() {
+                    held_on_keyboard.release(&mut input, *key);
+                }
+                pause(
+                    &mut state,
+                    &mut pause_reason,
+                    PauseReason::GamepadDisconnected,
+                );
+                toasts.send(Toast::new(ToastIcon::Info, "Controller disconnected"));
+            }
+            GamepadEventType::ButtonChanged(_, value) if *value > 0.5 => {
+                active_gamepad.0 = Some(*gamepad);
+            }
+            _ => {}
+        }
+    }
+}
+
+///
+/// Buttons only press keys during play, so a button that picks a menu item
+/// doesn't also act in the game. Releases always go through, so no key is left
+/// held after a pause, unless the key is also held on the keyboard.
+///
+// system
+fn gamepad_keys(
+    state: Res<State<GameState>>,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut held_on_keyboard: ResMut<HeldOnKeyboard>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    mut input: ResMut<Input<KeyCode>>,
+) {
+    for event in keyboard_events.iter() {
+        if let Some(key_code) = event.key_code {
+            match event.state {
+                ElementState::Pressed => held_on_keyboard.0.insert(key_code),
+                ElementState::Released => held_on_keyboard.0.remove(&key_code),
+            };
+        }
+    }
+
+    let playing = *state.current() == GameState::Playing;
+
+    for (button_type, key) in GAMEPAD_KEYS.iter() {
+        let is_button = |GamepadButton(_, button): &GamepadButton| button == button_type;
+
+        if playing && gamepad_input.get_just_pressed().any(is_button) {
+            input.press(*key);
+        }
+        if gamepad_input.get_just_released().any(is_button) {
+            held_on_keyboard.release(&mut input, *key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyboard_keeps_its_keys() {
+        let mut input = Input::<KeyCode>::default();
+        let mut held_on_keyboard = HeldOnKeyboard::default();
+
+        // Left held on the keyboard while the D-pad taps it
+        input.press(KeyCode::Left);
+        held_on_keyboard.0.insert(KeyCode::Left);
+        held_on_keyboard.release(&mut input, KeyCode::Left);
+        assert!(input.pressed(KeyCode::Left));
+
+        // Only the D-pad held it
+        input.press(KeyCode::Right);
+        held_on_keyboard.release(&mut input, KeyCode::Right);
+        assert!(!input.pressed(KeyCode::Right));
+    }
+}
@@ -0,0 +1,104 @@
+//
+// HUD
+//
+// Score, level and cleared lines from score.rs, in the top left corner under
+// the game timer. The preview and toasts have the right side of the screen,
+// and the debug overlay the bottom left corner.
+//
+
+use bevy::prelude::*;
+
+use crate::score::Score;
+use crate::FONT_PATH;
+
+const MARGIN: f32 = 10.0;
+
+/// Clears the game timer, see game_timer.rs
+const TOP: f32 = 52.0;
+
+#[derive(Clone, Copy)]
+enum HudStat {
+    Score,
+    Level,
+    Lines,
+}
+
+impl HudStat {
+    /// Top to bottom
+    const ALL: [Self; 3] = [Self::Score, Self::Level, Self::Lines];
+
+    fn text(&self, score: &Score) -> String {
+        match self {
+            Self::Score => format!("Score {}", score.points),
+            Self::Level => format!("Level {}", score.level),
+            Self::Lines => format!("Lines {}", score.lines),
+        }
+    }
+}
+
+/// One line of HUD text
+struct HudText(HudStat);
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup_hud.system())
+            .add_system(show_score.system());
+    }
+}
+
+// startup system
+fn setup_hud(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let font = asset_server.load(FONT_PATH);
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(TOP),
+                    left: Val::Px(MARGIN),
+                    ..Default::default()
+                },
+                // Bevy UI's y axis points up, so "reverse" means top to bottom
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            for stat in HudStat::ALL.iter() {
+                parent
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            stat.text(&Score::default()),
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 24.0,
+                                color: Color::WHITE,
+                            },
+                            TextAlignment::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(HudText(*stat));
+            }
+        });
+}
+
+// system
+fn show_score(score: Res<Score>, mut text_query: Query<(&HudText, &mut Text)>) {
+    if !score.is_changed() {
+        return;
+    }
+
+    for (HudText(stat), mut text) in text_query.iter_mut() {
+        text.sections[0].value = stat.text(&score);
+    }
+}
@@ -0,0 +1,267 @@
+//
+// Input latency overlay, enabled with `--features debug-input-latency`
+//
+// Measures how long a Left or Right press takes to have an effect, for
+// checking that input is handled as early as it can be (see frame_pacing.rs):
+//  * input to force: until `tetromino_movement` has pushed the current tetromino
+//  * input to visible: until the tetromino's `Transform` has moved that way,
+//    measured at the end of the frame it was rendered in
+//
+// Times start when the frame that sees the key event begins, so the time the
+// event waited for that frame, and the time until the display shows the
+// rendered frame, are not included.
+//
+// F4 toggles the overlay.
+//
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::ElementState;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{Game, FONT_PATH, MOVEMENT};
+
+/// Number of measurements averaged
+const N_SAMPLES: usize = 30;
+
+/// Give up on a press that hasn't moved anything after this many frames
+const TIMEOUT_FRAMES: u32 = 120;
+
+#[derive(Clone, Copy)]
+struct Sample {
+    frames: u32,
+    time: Duration,
+}
+
+#[derive(Default)]
+struct Samples(VecDeque<Sample>);
+
+impl Samples {
+    fn push(&mut self, sample: Sample) {
+        if self.0.len() == N_SAMPLES {
+            self.0.pop_front();
+        }
+        self.0.push_back(sample);
+    }
+
+    fn describe(&self) -> String {
+        match self.0.back() {
+            Some(last) => {
+                let n = self.0.len() as u32;
+                let frames = self.0.iter().map(|sample| sample.frames).sum::<u32>() as f32;
+                let time = self.0.iter().map(|sample| sample.time).sum::<Duration>() / n;
+
+                format!(
+                    "{} frames, {:.1} ms (average of {}: {:.1} frames, {:.1} ms)",
+                    last.frames,
+                    last.time.as_secs_f64() * 1000.0,
+                    n,
+                    frames / n as f32,
+                    time.as_secs_f64() * 1000.0,
+                )
+            }
+            None => "-".to_string(),
+        }
+    }
+}
+
+/// A press being measured
+struct PendingInput {
+    /// -1 for Left, 1 for Right
+    direction: f32,
+    frame: u32,
+    started: Instant,
+    /// Average x of the current tetromino's blocks when the press was seen
+    start_x: Option<f32>,
+    force_applied: bool,
+}
+
+impl PendingInput {
+    fn sample(&self, frame: u32) -> Sample {
+        Sample {
+            frames: frame - self.frame,
+            time: self.started.elapsed(),
+        }
+    }
+}
+
+/// Resource: Res<InputLatency>
+#[derive(Default)]
+struct InputLatency {
+    frame: u32,
+    pending: Option<PendingInput>,
+    to_force: Samples,
+    to_visible: Samples,
+}
+
+struct InputLatencyText;
+
+pub struct InputLatencyPlugin;
+
+impl Plugin for InputLatencyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<InputLatency>()
+            .add_startup_system(setup_input_latency_text.system())
+            .add_system_to_stage(CoreStage::First, start_measurement.system())
+            .add_system(measure_force.system().after(MOVEMENT))
+            .add_system_to_stage(CoreStage::Last, measure_movement.system())
+            .add_system(toggle_input_latency_text.system())
+            .add_system(show_input_latency.system());
+    }
+}
+
+// startup system
+fn setup_input_latency_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(40.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(FONT_PATH),
+                    font_size: 16.0,
+                    color: Color::rgb(1.0, 1.0, 0.0),
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(InputLatencyText);
+}
+
+///
+/// Average x of the current tetromino's blocks, in pixels
+///
+fn current_tetromino_x(game: &Game, transform_query: &Query<&Transform>) -> Option<f32> {
+    let xs: Vec<f32> = game
+        .current_tetromino_blocks
+        .iter()
+        .filter_map(|block_entity| transform_query.get(*block_entity).ok())
+        .map(|transform| transform.translation.x)
+        .collect();
+
+    if xs.is_empty() {
+        None
+    } else {
+        Some(xs.iter().sum::<f32>() / xs.len() as f32)
+    }
+}
+
+// system
+fn start_measurement(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    game: Res<Game>,
+    mut latency: ResMut<InputLatency>,
+    transform_query: Query<&Transform>,
+) {
+    latency.frame += 1;
+
+    let direction = keyboard_events
+        .iter()
+        .filter(|event| event.state == ElementState::Pressed)
+        .filter_map(|event| match event.key_code {
+            Some(KeyCode::Left) => Some(-1.0),
+            Some(KeyCode::Right) => Some(1.0),
+            _ => None,
+        })
+        .last();
+
+    if let Some(direction) = direction {
+        latency.pending = Some(PendingInput {
+            direction,
+            frame: latency.frame,
+            started: Instant::now(),
+            start_x: current_tetromino_x(&game, &transform_query),
+            force_applied: false,
+        });
+    }
+}
+
+// system
+fn measure_force(
+    game: Res<Game>,
+    mut latency: ResMut<InputLatency>,
+    forces_query: Query<&RigidBodyForces>,
+) {
+    let latency = &mut *latency;
+    let pending = match &mut latency.pending {
+        Some(pending) if !pending.force_applied => pending,
+        _ => return,
+    };
+
+    let pushed = game.current_tetromino_blocks.iter().any(|block_entity| {
+        forces_query
+            .get(*block_entity)
+            .map(|forces| forces.force.x * pending.direction > 0.0)
+            .unwrap_or(false)
+    });
+
+    if pushed {
+        pending.force_applied = true;
+        latency.to_force.push(pending.sample(latency.frame));
+    }
+}
+
+// system
+fn measure_movement(
+    game: Res<Game>,
+    mut latency: ResMut<InputLatency>,
+    transform_query: Query<&Transform>,
+) {
+    let pending = match latency.pending.take() {
+        Some(pending) => pending,
+        None => return,
+    };
+
+    let moved = match (
+        pending.start_x,
+        current_tetromino_x(&game, &transform_query),
+    ) {
+        (Some(start_x), Some(x)) => (x - start_x) * pending.direction > 0.0,
+        _ => false,
+    };
+
+    if moved {
+        let sample = pending.sample(latency.frame);
+        latency.to_visible.push(sample);
+    } else if latency.frame - pending.frame < TIMEOUT_FRAMES {
+        latency.pending = Some(pending);
+    }
+}
+
+// system
+fn toggle_input_latency_text(
+    input: Res<Input<KeyCode>>,
+    mut text_query: Query<&mut Visible, With<InputLatencyText>>,
+) {
+    if input.just_pressed(KeyCode::F4) {
+        for mut visible in text_query.iter_mut() {
+            visible.is_visible = !visible.is_visible;
+        }
+    }
+}
+
+// system
+fn show_input_latency(
+    latency: Res<InputLatency>,
+    mut text_query: Query<&mut Text, With<InputLatencyText>>,
+) {
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = format!(
+            "input to force: {}\ninput to visible: {}",
+            latency.to_force.describe(),
+            latency.to_visible.describe(),
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//
+// Line clearing
+//
+// Each time a tetromino locks, i.e. comes to rest and the next one spawns, the
+// full rows of the occupancy grid in board_grid.rs are cleared. The grid puts
+// every block in the cell its center is in, which is too lenient here: a row
+// is only cleared if the center of each of its blocks is within
+// `ROW_TOLERANCE` of the middle of the row, so a block resting across two rows
+// fills neither. The blocks of a cleared row are despawned, and the blocks
+// above are woken up and fall into the gap under gravity, which may well not
+// leave them as neatly stacked as before.
+//
+// Every lock sends `LinesCleared`, with how many rows it cleared, for score.rs.
+//
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::board_grid::BoardGrid;
+use crate::toast::{Toast, ToastIcon};
+use crate::{Block, Game, GameState, PieceLocked, BOARD_GRID, LINE_CLEARING};
+
+/// In terms of block size
+const ROW_TOLERANCE: f32 = 0.25;
+
+/// Event: Sent for every lock, with the number of rows it cleared
+pub struct LinesCleared(pub usize);
+
+pub struct LineClearingPlugin;
+
+impl Plugin for LineClearingPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<LinesCleared>().add_system_set(
+            SystemSet::on_update(GameState::Playing).with_system(
+                line_clearing
+                    .system()
+                    .label(LINE_CLEARING)
+                    .after(BOARD_GRID),
+            ),
+        );
+    }
+}
+
+///
+/// The row a block at height `y` above the floor counts towards, if its center
+/// is close enough to the middle of one
+///
+fn bin_row(y: f32) -> Option<i32> {
+    let row = (y - 0.5).round();
+
+    if (y - 0.5 - row).abs() <= ROW_TOLERANCE && row >= 0.0 {
+        Some(row as i32)
+    } else {
+        None
+    }
+}
+
+// system
+fn line_clearing(
+    mut commands: Commands,
+    game: Res<Game>,
+    grid: Res<BoardGrid>,
+    mut locked_events: EventReader<PieceLocked>,
+    mut cleared_events: EventWriter<LinesCleared>,
+    mut toasts: EventWriter<Toast>,
+    mut block_query: Query<(Entity, &RigidBodyPosition, &mut RigidBodyActivation), With<Block>>,
+) {
+    if locked_events.iter().count() == 0 {
+        return;
+    }
+
+    let mut full_rows = grid.full_rows();
+    let mut row_blocks: HashMap<i32, Vec<Entity>> = HashMap::new();
+
+    for (block_entity, position, _) in block_query.iter() {
+        if game.current_tetromino_blocks.contains(&block_entity) {
+            continue;
+        }
+
+        let translation = position.position.translation;
+        let (_, row) = game.physics_to_board((translation.x, translation.y));
+        if !full_rows.contains(&row) {
+            continue;
+        }
+
+        if bin_row(translation.y - game.floor_y()) == Some(row) {
+            row_blocks.entry(row).or_default().push(block_entity);
+        } else {
+            full_rows.retain(|full_row| *full_row != row);
+        }
+    }
+
+    cleared_events.send(LinesCleared(full_rows.len()));
+
+    let lowest_full_row = match full_rows.iter().min() {
+        Some(row) => *row,
+        None => return,
+    };
+
+    for row in &full_rows {
+        for block_entity in &row_blocks[row] {
+            commands.entity(*block_entity).despawn_recursive();
+        }
+    }
+
+    // Resting blocks stay asleep, even with nothing under them any more
+    let lowest_full_row_y = game.floor_y() + lowest_full_row as f32 + 0.5;
+    for (block_entity, position, mut activation) in block_query.iter_mut() {
+        if position.position.translation.y > lowest_full_row_y
+            && !game.current_tetromino_blocks.contains(&block_entity)
+        {
+            activation.wake_up(true);
+        }
+    }
+
+    toasts.send(Toast::new(
+        ToastIcon::Info,
+        match full_rows.len() {
+            1 => "Line clear!".to_string(),
+            n => format!("{} lines cleared!", n),
+        },
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bin_row_in_the_middle_of_a_row() {
+        assert_eq!(bin_row(0.5), Some(0));
+        assert_eq!(bin_row(3.5), Some(3));
+    }
+
+    #[test]
+    fn bin_row_within_tolerance() {
+        assert_eq!(bin_row(0.5 + ROW_TOLERANCE), Some(0));
+        assert_eq!(bin_row(1.5 - ROW_TOLERANCE), Some(1));
+        assert_eq!(bin_row(1.5 + ROW_TOLERANCE), Some(1));
+    }
+
+    #[test]
+    fn bin_row_outside_tolerance() {
+        assert_eq!(bin_row(0.5 + ROW_TOLERANCE + 0.01), None);
+        assert_eq!(bin_row(1.5 - ROW_TOLERANCE - 0.01), None);
+        // Halfway between two rows
+        assert_eq!(bin_row(1.0), None);
+        assert_eq!(bin_row(2.0), None);
+    }
+
+    #[test]
+    fn bin_row_below_the_floor() {
+        // Sunk a little into the floor still counts as the bottom row
+        assert_eq!(bin_row(0.5 - ROW_TOLERANCE), Some(0));
+        assert_eq!(bin_row(0.5 - ROW_TOLERANCE - 0.01), None);
+        // There are no negative rows
+        assert_eq!(bin_row(-0.5), None);
+        assert_eq!(bin_row(-1.5), None);
+    }
+}
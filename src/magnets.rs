@@ -0,0 +1,117 @@
+//
+// Magnetized blocks modifier
+//
+// With `--magnets`, blocks of the same color pull on each other with a weak
+// inverse-square force, as long as they are within `RADIUS` of each other.
+// Nearby blocks are found with a shape query on the physics query pipeline,
+// and every block pulls on every other one it finds, so each pair attracts
+// both ways. Like colors clump together, and the stack bends towards them.
+//
+// Sleeping blocks are left alone, or the stack would never come to rest and
+// the next tetromino would never spawn.
+//
+// The flag is one of the modifiers in modifiers.rs, so it's part of share
+// codes, and replays recorded with it can't be played back without it.
+//
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::geometry::InteractionGroups;
+use bevy_rapier2d::rapier::math::Isometry;
+use bevy_rapier2d::rapier::pipeline::QueryPipeline;
+
+use crate::modifiers::Modifiers;
+use crate::{Block, GameState, MOVEMENT};
+
+/// In terms of block size
+const RADIUS: f32 = 2.5;
+
+/// Closer than this, the pull doesn't grow any stronger. Neighbouring blocks
+/// touch at a distance of 1.
+const MIN_DISTANCE: f32 = 1.0;
+
+/// Force between two blocks of mass 1 at a distance of 1
+const STRENGTH: f32 = 4.0;
+
+pub struct MagnetsPlugin;
+
+impl Plugin for MagnetsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if !Modifiers::of_app(app).magnets {
+            return;
+        }
+
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                // Movement sets the force rather than adding to it
+                .with_system(magnetize_blocks.system().after(MOVEMENT)),
+        );
+    }
+}
+
+// system
+fn magnetize_blocks(
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    mut block_query: Query<(
+        Entity,
+        &Block,
+        &RigidBodyActivation,
+        &RigidBodyMassProps,
+        &mut RigidBodyForces,
+    )>,
+) {
+    let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+    let search_shape = ColliderShape::ball(RADIUS);
+
+    let mut pulls = vec![];
+
+    for (block_entity, block, activation, mass_props, _) in block_query.iter() {
+        if block.kind.is_none() || activation.sleeping {
+            continue;
+        }
+
+        let com = mass_props.world_com;
+        let mut pull = Vec2::ZERO;
+
+        query_pipeline.intersections_with_shape(
+            &collider_set,
+            &Isometry::translation(com.x, com.y),
+            &*search_shape,
+            InteractionGroups::all(),
+            None,
+            |handle| {
+                let other_entity = handle.entity();
+                if other_entity == block_entity {
+                    return true;
+                }
+
+                if let Ok((_, other_block, _, other_mass_props, _)) = block_query.get(other_entity)
+                {
+                    if other_block.kind == block.kind {
+                        let other_com = other_mass_props.world_com;
+                        let offset = Vec2::new(other_com.x - com.x, other_com.y - com.y);
+                        let distance = offset.length().max(MIN_DISTANCE);
+                        let strength = STRENGTH
+                            * mass_props.local_mprops.mass()
+                            * other_mass_props.local_mprops.mass()
+                            / (distance * distance);
+
+                        pull += offset.normalize_or_zero() * strength;
+                    }
+                }
+
+                true
+            },
+        );
+
+        pulls.push((block_entity, pull));
+    }
+
+    for (block_entity, pull) in pulls {
+        if let Ok((_, _, _, _, mut forces)) = block_query.get_mut(block_entity) {
+            forces.force.x += pull.x;
+            forces.force.y += pull.y;
+        }
+    }
+}
@@ -1,21 +1,182 @@
+mod adaptive_difficulty;
+mod board_code;
+mod board_grid;
+mod camera;
+mod center_of_mass;
+mod collapse;
+mod conveyor;
+mod damping_zones;
+mod danger_gradient;
+#[cfg(feature = "debug-overlay")]
+mod debug_overlay;
+mod entity_budget;
+mod event_log;
+mod floor_gaps;
+mod frame_pacing;
+mod game_over;
+mod game_timer;
+mod gamepad;
+mod hud;
+#[cfg(feature = "debug-input-latency")]
+mod input_latency;
+mod line_clearing;
+mod magnets;
+mod menu;
+mod modifiers;
+mod out_of_play;
+mod outline;
+mod pause;
+mod physics_guard;
+mod preview;
+mod quick_restart;
+mod randomizer;
+mod replay;
+mod rotation_control;
+mod score;
+mod script;
+mod settle;
+mod share_code;
+mod shrinking_board;
+#[cfg(feature = "debug-time-scale")]
+mod time_scale;
+mod title_screen;
+mod toast;
+mod tuning;
+mod velocity_control;
+
 use std::collections::HashSet;
+use std::f32::consts::FRAC_PI_2;
 
 use bevy::prelude::*;
 use bevy::render::pass::ClearColor;
+use bevy::window::WindowPlugin;
 use bevy_rapier2d::prelude::*;
-use rand::Rng;
+use bevy_rapier2d::rapier::dynamics::{BallJoint, IntegrationParameters};
+use bevy_rapier2d::rapier::math::Point;
+use board_grid::BoardGrid;
+use game_over::{game_over, GameOverReason};
+use modifiers::Modifiers;
+use randomizer::PieceBag;
+use rotation_control::RotationTarget;
+use score::Score;
+use tuning::Tuning;
 
 fn main() {
+    let mut app = App::build();
+    let tuning = Tuning::from_args();
+    let game = replay::init_from_args(&mut app, &tuning);
+
+    // For streaming: a flat background that can be keyed out in OBS
+    let clear_color = if has_flag("--chroma-key") {
+        CHROMA_KEY_COLOR
+    } else {
+        Color::rgb(0.0, 0.0, 0.0)
+    };
+
     // Set up Bevy
-    App::build()
-        .init_resource::<Game>()
-        .insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
+    app.insert_resource(game)
+        .insert_resource(tuning)
+        .register_type::<Tuning>()
+        .insert_resource(ClearColor(clear_color))
         .insert_resource(Msaa::default())
-        .add_plugins(DefaultPlugins)
+        .add_plugin(frame_pacing::FramePacingPlugin)
+        // Closing the window mid-game asks for confirmation, see pause.rs
+        .add_plugin(WindowPlugin {
+            add_primary_window: true,
+            exit_on_close: false,
+        })
+        .add_plugins_with(DefaultPlugins, |group| group.disable::<WindowPlugin>())
+        .add_state(title_screen::initial_state())
+        .add_event::<Restart>()
+        .add_event::<PieceLocked>()
         .add_startup_system(setup_game.system())
-        .add_system(tetromino_movement.system())
+        .add_system(restart_game.system().label(RESTART))
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(tetromino_movement.system().label(MOVEMENT))
+                .with_system(tetromino_rotation.system())
+                .with_system(tetromino_gravity.system().after(SCORE))
+                .with_system(tetromino_rotation_snap.system())
+                .with_system(tetromino_velocity_clamp.system())
+                .with_system(tetromino_sleep_detection.system().label(LOCK)),
+        )
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-        .run();
+        .add_plugin(adaptive_difficulty::AdaptiveDifficultyPlugin)
+        .add_plugin(board_code::BoardCodePlugin)
+        .add_plugin(board_grid::BoardGridPlugin)
+        .add_plugin(camera::CameraPlugin)
+        .add_plugin(center_of_mass::CenterOfMassPlugin)
+        .add_plugin(collapse::CollapsePlugin)
+        .add_plugin(conveyor::ConveyorPlugin)
+        .add_plugin(danger_gradient::DangerGradientPlugin)
+        .add_plugin(damping_zones::DampingZonePlugin)
+        .add_plugin(entity_budget::EntityBudgetPlugin)
+        .add_plugin(event_log::EventLogPlugin)
+        .add_plugin(floor_gaps::FloorGapsPlugin)
+        .add_plugin(game_over::GameOverPlugin)
+        .add_plugin(game_timer::GameTimerPlugin)
+        .add_plugin(gamepad::GamepadPlugin)
+        .add_plugin(hud::HudPlugin)
+        .add_plugin(line_clearing::LineClearingPlugin)
+        .add_plugin(magnets::MagnetsPlugin)
+        .add_plugin(menu::MenuPlugin)
+        .add_plugin(out_of_play::OutOfPlayPlugin)
+        .add_plugin(outline::OutlinePlugin)
+        .add_plugin(pause::PausePlugin)
+        .add_plugin(physics_guard::PhysicsGuardPlugin)
+        .add_plugin(preview::PreviewPlugin)
+        .add_plugin(quick_restart::QuickRestartPlugin)
+        .add_plugin(replay::ReplayPlugin)
+        .add_plugin(rotation_control::RotationControlPlugin)
+        .add_plugin(score::ScorePlugin)
+        .add_plugin(script::ScriptPlugin)
+        .add_plugin(settle::SettlePlugin)
+        .add_plugin(shrinking_board::ShrinkingBoardPlugin)
+        .add_plugin(title_screen::TitleScreenPlugin)
+        .add_plugin(toast::ToastPlugin)
+        .add_plugin(velocity_control::VelocityControlPlugin);
+
+    #[cfg(feature = "debug-overlay")]
+    app.add_plugin(debug_overlay::DebugOverlayPlugin);
+    #[cfg(feature = "debug-input-latency")]
+    app.add_plugin(input_latency::InputLatencyPlugin);
+    #[cfg(feature = "debug-time-scale")]
+    app.add_plugin(time_scale::TimeScalePlugin);
+
+    app.run();
+}
+
+fn has_flag(flag: &str) -> bool {
+    std::env::args().any(|arg| arg == flag)
+}
+
+///
+/// Look up the value following a command line flag, e.g. `--replay my.replay`
+///
+fn arg_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != flag);
+    args.next()?;
+    args.next()
+}
+
+///
+/// Apply `--board-size <lanes>x<rows>` to a new game
+///
+fn apply_board_size_arg(game: &mut Game) {
+    if let Some(size) = arg_value("--board-size") {
+        let mut dimensions = size.split('x').map(|n| n.parse::<usize>());
+
+        match (dimensions.next(), dimensions.next()) {
+            (Some(Ok(n_lanes)), Some(Ok(n_rows))) if n_lanes >= MIN_LANES && n_rows >= MIN_ROWS => {
+                game.n_lanes = n_lanes;
+                game.n_rows = n_rows;
+            }
+            _ => println!(
+                "Ignoring --board-size {}, expected e.g. 10x40, at least {}x{}",
+                size, MIN_LANES, MIN_ROWS
+            ),
+        }
+    }
 }
 
 //
@@ -41,7 +202,7 @@ fn main() {
 //
 // 4. Screen coordinate system
 //    Pixels on the screen!
-//    This is the Physics coordinate system scaled up by BLOCK_PX_SIZE.
+//    This is the Physics coordinate system scaled up by `Tuning::block_px_size`.
 //    So the center of the board is also the center of the screen.
 //
 // It is not recommended to put large numbers into the physics engine,
@@ -50,15 +211,55 @@ fn main() {
 // screen coordinates.
 //
 
-const BLOCK_PX_SIZE: f32 = 30.0;
+const FONT_PATH: &str = "fonts/DejaVuSans-Bold.ttf";
+
+/// Magenta rather than the usual green, which would key out the S tetromino
+const CHROMA_KEY_COLOR: Color = Color::rgb(1.0, 0.0, 1.0);
 
-// In terms of block size:
-const FLOOR_BLOCK_HEIGHT: f32 = 2.0;
+/// The narrowest board the game can be played on: room for an I piece
+const MIN_LANES: usize = 4;
 
-const BLOCK_LINEAR_DAMPING: f32 = 1.0;
+/// The lowest board the game can be played on: room for an I piece standing up
+const MIN_ROWS: usize = 4;
+
+/// Event: Throw away the current game and start a new one
+struct Restart;
+
+/// Label of the system handling `Restart`, for systems that need to see the new `Game`
+const RESTART: &str = "restart";
+
+/// Label of the system pushing the current tetromino sideways
+const MOVEMENT: &str = "movement";
+
+/// Event: The current tetromino came to rest and is now part of the stack
+struct PieceLocked {
+    /// Ordered by entity
+    blocks: Vec<Entity>,
+}
 
-const MOVEMENT_FORCE: f32 = 20.0;
-const TORQUE: f32 = 20.0;
+/// Label of the system sending `PieceLocked`
+const LOCK: &str = "lock";
+
+/// Label of the system updating `BoardGrid`, see board_grid.rs
+const BOARD_GRID: &str = "board_grid";
+
+/// Label of the system clearing full rows, see line_clearing.rs
+const LINE_CLEARING: &str = "line_clearing";
+
+/// Label of the system updating `Score`, see score.rs
+const SCORE: &str = "score";
+
+/// Bevy state: Res<State<GameState>>
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum GameState {
+    /// The title screen, see title_screen.rs
+    Menu,
+    Playing,
+    /// Pushed on top of `Playing`, see pause.rs
+    Paused,
+    /// See game_over.rs
+    GameOver,
+}
 
 /// Type for our discrete coordinate systems
 /// (column, row) or (x, y)
@@ -68,13 +269,65 @@ type IVector = (i32, i32);
 struct Game {
     n_lanes: usize,
     n_rows: usize,
-    block_color: Option<Handle<ColorMaterial>>,
+    /// Of the walls on both sides of the board, in terms of block size.
+    /// 0 leaves the sides open.
+    wall_thickness: f32,
+    /// See modifiers.rs
+    modifiers: Modifiers,
+    /// Blocks every game starts with, see board_grid.rs
+    starting_board: Option<BoardGrid>,
+    /// One per `TetrominoKind`, in `TetrominoKind::ALL` order, followed by the
+    /// color of blocks that aren't part of any tetromino. Empty until setup_game.
+    block_colors: Vec<Handle<ColorMaterial>>,
     current_tetromino_blocks: HashSet<Entity>,
     current_tetromino_joints: Vec<Entity>,
     camera: Option<Entity>,
+    /// The seed `pieces` was created from. Stored in replays.
+    seed: u64,
+    /// All randomness in the game must come from the seed, or replays break
+    pieces: PieceBag,
+    /// Dealt before the pieces from the seed, see board_code.rs. Only for the
+    /// first game, a restart deals from the seed only.
+    dealt_first: Vec<TetrominoKind>,
+    /// Tetrominos spawned since the game started
+    n_pieces: usize,
+    current_tetromino_kind: Option<TetrominoKind>,
 }
 
 impl Game {
+    fn with_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            pieces: PieceBag::new(seed),
+            ..Self::default()
+        }
+    }
+
+    ///
+    /// A new game on the same board, with a new seed
+    ///
+    fn restarted(&self) -> Self {
+        Self {
+            n_lanes: self.n_lanes,
+            n_rows: self.n_rows,
+            wall_thickness: self.wall_thickness,
+            modifiers: self.modifiers,
+            starting_board: self.starting_board.clone(),
+            block_colors: self.block_colors.clone(),
+            camera: self.camera,
+            ..Self::default()
+        }
+    }
+
+    ///
+    /// The material of a tetromino kind's blocks, or of blocks that aren't part
+    /// of a tetromino for `None`
+    ///
+    fn block_color(&self, kind: Option<TetrominoKind>) -> Option<Handle<ColorMaterial>> {
+        let index = kind.map_or(TetrominoKind::ALL.len(), |kind| kind as usize);
+        self.block_colors.get(index).cloned()
+    }
+
     ///
     /// The y position of the floor, in physics coordinates
     ///
@@ -94,8 +347,11 @@ impl Game {
     /// where tetrominos should spawn!
     ///
     fn translate_to_board_center_top(&self, (col, row): IVector) -> IVector {
-        // Task: FIXME!
-        (col, row)
+        // Tetromino layouts span columns -1..=2 and rows 0..=1
+        (
+            col + self.n_lanes as i32 / 2 - 1,
+            row + self.n_rows as i32 - 2,
+        )
     }
 
     ///
@@ -107,17 +363,37 @@ impl Game {
 
         (x, y)
     }
+
+    ///
+    /// Translate from physics coordinates to the board cell containing them.
+    ///
+    fn physics_to_board(&self, (x, y): (f32, f32)) -> IVector {
+        let col = (x - self.left_edge_x()).floor() as i32;
+        let row = (y - self.floor_y()).floor() as i32;
+
+        (col, row)
+    }
 }
 
 impl Default for Game {
     fn default() -> Self {
+        let seed = rand::random();
+
         Self {
             n_lanes: 10,
             n_rows: 20,
-            block_color: None,
+            wall_thickness: 0.5,
+            modifiers: Modifiers::default(),
+            starting_board: None,
+            block_colors: vec![],
             current_tetromino_blocks: HashSet::new(),
             current_tetromino_joints: vec![],
             camera: None,
+            seed,
+            pieces: PieceBag::new(seed),
+            dealt_first: vec![],
+            n_pieces: 0,
+            current_tetromino_kind: None,
         }
     }
 }
@@ -127,10 +403,16 @@ fn setup_game(
     mut game: ResMut<Game>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut rapier_config: ResMut<RapierConfiguration>,
+    tuning: Res<Tuning>,
 ) {
-    rapier_config.scale = BLOCK_PX_SIZE;
+    rapier_config.scale = tuning.block_px_size;
 
-    game.block_color = Some(materials.add(Color::rgb_u8(0, 244, 243).into()));
+    game.block_colors = TetrominoKind::ALL
+        .iter()
+        .map(|kind| kind.color())
+        .chain(std::iter::once(Color::rgb(0.6, 0.6, 0.6)))
+        .map(|color| materials.add(color.into()))
+        .collect();
 
     game.camera = Some(
         commands
@@ -138,15 +420,40 @@ fn setup_game(
             .insert_bundle(OrthographicCameraBundle::new_2d())
             .id(),
     );
+    commands.spawn_bundle(UiCameraBundle::default());
 
-    setup_board(&mut commands, &*game, materials);
+    setup_board(&mut commands, &*game, &*tuning, materials);
 
     // initial tetromino
-    spawn_tetromino(&mut commands, &mut game);
+    spawn_tetromino(&mut commands, &mut game, &*tuning);
+}
+
+// system
+fn restart_game(
+    mut commands: Commands,
+    mut restart_events: EventReader<Restart>,
+    mut game: ResMut<Game>,
+    tuning: Res<Tuning>,
+    block_query: Query<Entity, With<Block>>,
+) {
+    if restart_events.iter().count() == 0 {
+        return;
+    }
+
+    for block_entity in block_query.iter() {
+        commands.entity(block_entity).despawn_recursive();
+    }
+    for joint in &game.current_tetromino_joints {
+        commands.entity(*joint).despawn();
+    }
+
+    *game = game.restarted();
+
+    spawn_tetromino(&mut commands, &mut game, &*tuning);
 }
 
 /// Represent Tetris' different tetromino kinds
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum TetrominoKind {
     I,
     O,
@@ -158,24 +465,104 @@ enum TetrominoKind {
 }
 
 impl TetrominoKind {
-    fn random() -> Self {
-        match rand::thread_rng().gen_range(0..7) {
-            0 => Self::I,
-            1 => Self::O,
-            2 => Self::T,
-            3 => Self::J,
-            4 => Self::L,
-            5 => Self::S,
-            _ => Self::Z,
+    const ALL: [Self; 7] = [
+        Self::I,
+        Self::O,
+        Self::T,
+        Self::J,
+        Self::L,
+        Self::S,
+        Self::Z,
+    ];
+
+    ///
+    /// Joints connect neighbouring blocks, so each layout lists the pairs of
+    /// `coords` that are next to each other.
+    ///
+    fn layout(&self) -> TetrominoLayout {
+        let (coords, joints) = match self {
+            // ####
+            Self::I => (
+                [(-1, 0), (0, 0), (1, 0), (2, 0)],
+                vec![(0, 1), (1, 2), (2, 3)],
+            ),
+            // ##
+            // ##
+            Self::O => (
+                [(0, 0), (1, 0), (0, 1), (1, 1)],
+                vec![(0, 1), (0, 2), (1, 3), (2, 3)],
+            ),
+            //  #
+            // ###
+            Self::T => (
+                [(-1, 0), (0, 0), (1, 0), (0, 1)],
+                vec![(0, 1), (1, 2), (1, 3)],
+            ),
+            // #
+            // ###
+            Self::J => (
+                [(-1, 1), (-1, 0), (0, 0), (1, 0)],
+                vec![(0, 1), (1, 2), (2, 3)],
+            ),
+            //   #
+            // ###
+            Self::L => (
+                [(-1, 0), (0, 0), (1, 0), (1, 1)],
+                vec![(0, 1), (1, 2), (2, 3)],
+            ),
+            //  ##
+            // ##
+            Self::S => (
+                [(-1, 0), (0, 0), (0, 1), (1, 1)],
+                vec![(0, 1), (1, 2), (2, 3)],
+            ),
+            // ##
+            //  ##
+            Self::Z => (
+                [(-1, 1), (0, 1), (0, 0), (1, 0)],
+                vec![(0, 1), (1, 2), (2, 3)],
+            ),
+        };
+
+        TetrominoLayout { coords, joints }
+    }
+
+    ///
+    /// The usual Tetris colors
+    ///
+    fn color(&self) -> Color {
+        match self {
+            Self::I => Color::rgb_u8(0, 244, 243),
+            Self::O => Color::rgb_u8(240, 240, 0),
+            Self::T => Color::rgb_u8(160, 0, 240),
+            Self::J => Color::rgb_u8(0, 0, 240),
+            Self::L => Color::rgb_u8(240, 160, 0),
+            Self::S => Color::rgb_u8(0, 240, 0),
+            Self::Z => Color::rgb_u8(240, 0, 0),
         }
     }
 
-    fn layout(&self) -> TetrominoLayout {
-        TetrominoLayout {
-            coords: [(0, 0), (1, 0), (2, 0), (1, -1)],
-            joints: vec![(0, 1), (1, 2), (1, 3)],
+    ///
+    /// The letter the kind is named after, e.g. for text boards
+    ///
+    fn letter(&self) -> char {
+        match self {
+            Self::I => 'I',
+            Self::O => 'O',
+            Self::T => 'T',
+            Self::J => 'J',
+            Self::L => 'L',
+            Self::S => 'S',
+            Self::Z => 'Z',
         }
     }
+
+    fn from_letter(letter: char) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.letter() == letter)
+    }
 }
 
 /// The layout of one tetromino
@@ -187,11 +574,29 @@ struct TetrominoLayout {
     joints: Vec<(usize, usize)>,
 }
 
-struct Block;
+struct Block {
+    /// `None` for blocks that didn't come from a tetromino, e.g. from `--board`
+    kind: Option<TetrominoKind>,
+    /// The `Game::n_pieces` count of the tetromino the block came from
+    piece: Option<usize>,
+}
+
+/// The floor of the board
+struct Floor;
+
+/// Marker: the walls on the sides of the board
+struct Wall;
 
 // startup system
-fn setup_board(commands: &mut Commands, game: &Game, mut materials: ResMut<Assets<ColorMaterial>>) {
+fn setup_board(
+    commands: &mut Commands,
+    game: &Game,
+    tuning: &Tuning,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
     let floor_y = game.floor_y();
+    let floor_block_height = tuning.floor_block_height;
+    let material = materials.add(Color::rgb(0.5, 0.5, 0.5).into());
 
     // Add a "floor" - something blocks collide with when they hit the bottom of the board.
     // The floor is a *static* rigid body. It has infinite mass, and should
@@ -199,79 +604,159 @@ fn setup_board(commands: &mut Commands, game: &Game, mut materials: ResMut<Asset
     commands
         .spawn()
         .insert_bundle(SpriteBundle {
-            material: materials.add(Color::rgb(0.5, 0.5, 0.5).into()),
+            material: material.clone(),
             sprite: Sprite::new(Vec2::new(
-                game.n_lanes as f32 * BLOCK_PX_SIZE,
-                FLOOR_BLOCK_HEIGHT * BLOCK_PX_SIZE,
+                game.n_lanes as f32 * tuning.block_px_size,
+                floor_block_height * tuning.block_px_size,
             )),
             ..Default::default()
         })
         .insert_bundle(RigidBodyBundle {
             body_type: bevy_rapier2d::prelude::RigidBodyType::Static,
-            position: [0.0, floor_y - (FLOOR_BLOCK_HEIGHT * 0.5)].into(),
+            position: [0.0, floor_y - (floor_block_height * 0.5)].into(),
             ..RigidBodyBundle::default()
         })
         .insert_bundle(ColliderBundle {
-            shape: ColliderShape::cuboid(game.n_lanes as f32 * 0.5, FLOOR_BLOCK_HEIGHT * 0.5),
+            shape: ColliderShape::cuboid(game.n_lanes as f32 * 0.5, floor_block_height * 0.5),
+            material: ColliderMaterial {
+                friction: tuning.floor_friction,
+                ..ColliderMaterial::default()
+            },
             ..ColliderBundle::default()
         })
-        .insert(RigidBodyPositionSync::Discrete);
+        .insert(RigidBodyPositionSync::Discrete)
+        .insert(Floor);
+
+    // Walls on both sides keep blocks from being pushed off the board. They
+    // reach from the bottom of the floor to the top of the board.
+    if game.wall_thickness > 0.0 {
+        let wall_height = game.n_rows as f32 + floor_block_height;
+        let wall_x = -game.left_edge_x() + game.wall_thickness * 0.5;
+        let wall_y = floor_y - floor_block_height + wall_height * 0.5;
+
+        for x in [-wall_x, wall_x].iter() {
+            commands
+                .spawn()
+                .insert_bundle(SpriteBundle {
+                    material: material.clone(),
+                    sprite: Sprite::new(Vec2::new(
+                        game.wall_thickness * tuning.block_px_size,
+                        wall_height * tuning.block_px_size,
+                    )),
+                    ..Default::default()
+                })
+                .insert_bundle(RigidBodyBundle {
+                    body_type: RigidBodyType::Static,
+                    position: [*x, wall_y].into(),
+                    ..RigidBodyBundle::default()
+                })
+                .insert_bundle(ColliderBundle {
+                    shape: ColliderShape::cuboid(game.wall_thickness * 0.5, wall_height * 0.5),
+                    material: ColliderMaterial {
+                        friction: tuning.floor_friction,
+                        ..ColliderMaterial::default()
+                    },
+                    ..ColliderBundle::default()
+                })
+                .insert(RigidBodyPositionSync::Discrete)
+                .insert(Wall);
+        }
+    }
 }
 
-fn spawn_tetromino(commands: &mut Commands, game: &mut Game) {
-    let kind = TetrominoKind::random();
+fn spawn_tetromino(commands: &mut Commands, game: &mut Game, tuning: &Tuning) {
+    let kind = game.pieces.next();
+    game.n_pieces += 1;
+    game.current_tetromino_kind = Some(kind);
     let TetrominoLayout { coords, joints } = kind.layout();
 
-    let block_entity = spawn_block(commands, game, kind, coords[0]);
+    let block_entities: Vec<Entity> = coords
+        .iter()
+        .map(|coord| spawn_block(commands, game, tuning, kind, *coord))
+        .collect();
 
-    /*
-    for (i, j) in joints {
-        let x_dir = coords[j].0 as f32 - coords[i].0 as f32;
-        let y_dir = coords[j].1 as f32 - coords[i].1 as f32;
+    let joint_entities: Vec<Entity> = joints
+        .iter()
+        .map(|(i, j)| {
+            let x_dir = coords[*j].0 as f32 - coords[*i].0 as f32;
+            let y_dir = coords[*j].1 as f32 - coords[*i].1 as f32;
 
-        let anchor_1 = Point2::new(x_dir * 0.5, y_dir * 0.5);
-        let anchor_2 = Point2::new(x_dir * -0.5, y_dir * -0.5);
+            let anchor_1 = Point::new(x_dir * 0.5, y_dir * 0.5);
+            let anchor_2 = Point::new(x_dir * -0.5, y_dir * -0.5);
 
-        commands.spawn((JointBuilderComponent::new(
-            BallJoint::new(anchor_1, anchor_2),
-            block_entities[i],
-            block_entities[j],
-        ),));
-    }
-    */
+            commands
+                .spawn()
+                .insert(JointBuilderComponent::new(
+                    BallJoint::new(anchor_1, anchor_2),
+                    block_entities[*i],
+                    block_entities[*j],
+                ))
+                .id()
+        })
+        .collect();
+
+    game.current_tetromino_blocks = block_entities.into_iter().collect();
+    game.current_tetromino_joints = joint_entities;
 }
 
 fn spawn_block(
     commands: &mut Commands,
     game: &Game,
+    tuning: &Tuning,
     kind: TetrominoKind,
     tetromino_coord: IVector,
 ) -> Entity {
-    let (x, y) = game.board_to_physics(game.translate_to_board_center_top(tetromino_coord));
+    spawn_board_block(
+        commands,
+        game,
+        tuning,
+        Block {
+            kind: Some(kind),
+            piece: Some(game.n_pieces),
+        },
+        game.translate_to_board_center_top(tetromino_coord),
+    )
+}
+
+///
+/// Spawn a block in the given cell of the board
+///
+fn spawn_board_block(
+    commands: &mut Commands,
+    game: &Game,
+    tuning: &Tuning,
+    block: Block,
+    board_coord: IVector,
+) -> Entity {
+    let (x, y) = game.board_to_physics(board_coord);
 
     println!("block physics coords: {}, {}", x, y);
 
     commands
         .spawn()
         .insert_bundle(SpriteBundle {
-            material: game.block_color.clone().unwrap(),
-            sprite: Sprite::new(Vec2::new(BLOCK_PX_SIZE, BLOCK_PX_SIZE)),
+            material: game.block_color(block.kind).unwrap(),
+            sprite: Sprite::new(Vec2::new(tuning.block_px_size, tuning.block_px_size)),
             ..Default::default()
         })
         .insert_bundle(RigidBodyBundle {
             position: [x, y].into(),
             damping: RigidBodyDamping {
-                linear_damping: BLOCK_LINEAR_DAMPING,
-                angular_damping: 0.0,
+                linear_damping: tuning.block_linear_damping,
+                angular_damping: tuning.block_angular_damping,
             },
             ..RigidBodyBundle::default()
         })
         .insert_bundle(ColliderBundle {
             shape: ColliderShape::cuboid(0.5, 0.5),
+            material: ColliderMaterial {
+                friction: tuning.block_friction,
+                ..ColliderMaterial::default()
+            },
             ..ColliderBundle::default()
         })
         .insert(RigidBodyPositionSync::Discrete)
-        .insert(Block)
+        .insert(block)
         .id()
 }
 
@@ -279,24 +764,172 @@ fn spawn_block(
 fn tetromino_movement(
     input: Res<Input<KeyCode>>,
     game: Res<Game>,
+    tuning: Res<Tuning>,
     mut forces_query: Query<&mut RigidBodyForces>,
 ) {
+    // velocity_control.rs is in charge
+    if tuning.velocity_control_p > 0.0 {
+        return;
+    }
+
     let movement = input.pressed(KeyCode::Right) as i8 - input.pressed(KeyCode::Left) as i8;
 
     for block_entity in &game.current_tetromino_blocks {
         if let Ok(mut forces) = forces_query.get_mut(*block_entity) {
             if movement != 0 {
-                forces.force = Vec2::new(movement as f32 * MOVEMENT_FORCE, 0.0).into();
+                forces.force = Vec2::new(movement as f32 * tuning.movement_force, 0.0).into();
+            }
+        }
+    }
+}
+
+///
+/// Turn the current tetromino counterclockwise while Up or Z is held, and
+/// clockwise while X is held
+///
+// system
+fn tetromino_rotation(
+    input: Res<Input<KeyCode>>,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    mut forces_query: Query<&mut RigidBodyForces>,
+) {
+    // rotation_control.rs is in charge
+    if tuning.rotation_control_p > 0.0 {
+        return;
+    }
+
+    let counterclockwise = input.pressed(KeyCode::Up) || input.pressed(KeyCode::Z);
+    let rotation = counterclockwise as i8 - input.pressed(KeyCode::X) as i8;
+    if rotation == 0 {
+        return;
+    }
+
+    for block_entity in &game.current_tetromino_blocks {
+        if let Ok(mut forces) = forces_query.get_mut(*block_entity) {
+            forces.torque += rotation as f32 * tuning.torque;
+        }
+    }
+}
+
+///
+/// Gravity on the current tetromino: ramped up over `gravity_ramp_seconds` after
+/// it spawns, and multiplied by `soft_drop_factor` while Down is held
+///
+// system
+fn tetromino_gravity(
+    input: Res<Input<KeyCode>>,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    score: Res<Score>,
+    integration_parameters: Res<IntegrationParameters>,
+    mut piece_age: Local<(u64, usize, f32)>,
+    mut scaled_blocks: Local<HashSet<Entity>>,
+    mut block_query: Query<(&mut RigidBodyForces, &mut RigidBodyVelocity)>,
+) {
+    // Counted in physics steps rather than wall clock time, so replays match
+    if (piece_age.0, piece_age.1) != (game.seed, game.n_pieces) {
+        *piece_age = (game.seed, game.n_pieces, 0.0);
+    } else {
+        piece_age.2 += integration_parameters.dt;
+    }
+    let age = piece_age.2;
+
+    let soft_drop = input.pressed(KeyCode::Down);
+    let infinite = tuning.soft_drop_factor.is_infinite();
+    let gravity_scale = if soft_drop && !infinite {
+        tuning.soft_drop_factor
+    } else if age < tuning.gravity_ramp_seconds {
+        let ramp = age / tuning.gravity_ramp_seconds;
+        tuning.gravity_ramp_start + (1.0 - tuning.gravity_ramp_start) * ramp
+    } else {
+        1.0
+    };
+    // Higher levels fall faster, see score.rs
+    let gravity_scale = gravity_scale * score.gravity_factor(tuning.level_gravity_step);
+
+    // Blocks that are no longer part of the current tetromino get normal gravity back
+    for block_entity in scaled_blocks.iter() {
+        if !game.current_tetromino_blocks.contains(block_entity) {
+            if let Ok((mut forces, _)) = block_query.get_mut(*block_entity) {
+                forces.gravity_scale = 1.0;
+            }
+        }
+    }
+    scaled_blocks.clone_from(&game.current_tetromino_blocks);
+
+    for block_entity in &game.current_tetromino_blocks {
+        if let Ok((mut forces, mut velocity)) = block_query.get_mut(*block_entity) {
+            if forces.gravity_scale != gravity_scale {
+                forces.gravity_scale = gravity_scale;
+            }
+            if soft_drop && infinite {
+                velocity.linvel.y = -tuning.max_vertical_velocity;
             }
         }
     }
 }
 
+// system
+fn tetromino_rotation_snap(
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    mut block_query: Query<(&RigidBodyPosition, &mut RigidBodyForces), Without<RotationTarget>>,
+) {
+    if tuning.rotation_snap == 0.0 {
+        return;
+    }
+
+    for block_entity in &game.current_tetromino_blocks {
+        if let Ok((position, mut forces)) = block_query.get_mut(*block_entity) {
+            let angle = position.position.rotation.angle();
+            let snapped_angle = (angle / FRAC_PI_2).round() * FRAC_PI_2;
+
+            forces.torque += (snapped_angle - angle) * tuning.rotation_snap;
+        }
+    }
+}
+
+// system
+fn tetromino_velocity_clamp(
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    mut velocity_query: Query<&mut RigidBodyVelocity>,
+) {
+    let max_x = tuning.max_horizontal_velocity;
+    let max_y = tuning.max_vertical_velocity;
+    let max_angular = tuning.max_angular_velocity;
+
+    for block_entity in &game.current_tetromino_blocks {
+        if let Ok(mut velocity) = velocity_query.get_mut(*block_entity) {
+            let x = velocity.linvel.x.clamp(-max_x, max_x);
+            let y = velocity.linvel.y.clamp(-max_y, max_y);
+            let angular = velocity.angvel.clamp(-max_angular, max_angular);
+
+            // Only write when clamping, so resting bodies aren't woken up
+            if x != velocity.linvel.x || y != velocity.linvel.y {
+                velocity.linvel = Vec2::new(x, y).into();
+            }
+            if angular != velocity.angvel {
+                velocity.angvel = angular;
+            }
+        }
+    }
+}
+
+///
+/// When the current tetromino comes to rest, spawn the next one, unless the
+/// stack has reached the rows it would spawn in
+///
 // system
 fn tetromino_sleep_detection(
     mut commands: Commands,
     mut game: ResMut<Game>,
-    block_query: Query<(Entity, &RigidBodyActivation, &RigidBodyPosition)>,
+    tuning: Res<Tuning>,
+    mut state: ResMut<State<GameState>>,
+    mut game_over_reason: ResMut<GameOverReason>,
+    mut locked_events: EventWriter<PieceLocked>,
+    block_query: Query<(Entity, &RigidBodyActivation, &RigidBodyPosition), With<Block>>,
 ) {
     let all_blocks_sleeping = game.current_tetromino_blocks.iter().all(|block_entity| {
         block_query
@@ -307,10 +940,87 @@ fn tetromino_sleep_detection(
     });
 
     if all_blocks_sleeping {
-        for joint in &game.current_tetromino_joints {
-            commands.entity(*joint).despawn();
+        for joint in game.current_tetromino_joints.drain(..) {
+            commands.entity(joint).despawn();
         }
+        let mut blocks: Vec<Entity> = game.current_tetromino_blocks.iter().copied().collect();
+        blocks.sort();
+        locked_events.send(PieceLocked { blocks });
+
+        // Tetrominos spawn in the top two rows, see translate_to_board_center_top
+        let spawn_rows_y = game.floor_y() + game.n_rows as f32 - 2.0;
+        let topped_out = block_query
+            .iter()
+            .any(|(_, _, position)| position.position.translation.y > spawn_rows_y);
+
+        if topped_out {
+            game_over(&mut state, &mut game_over_reason, GameOverReason::ToppedOut);
+        } else {
+            spawn_tetromino(&mut commands, &mut game, &*tuning);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(n_lanes: usize, n_rows: usize) -> Game {
+        Game {
+            n_lanes,
+            n_rows,
+            ..Game::with_seed(0)
+        }
+    }
+
+    /// Offsets from the center of a cell, in each direction, that stay inside it
+    const CELL_OFFSETS: [f32; 5] = [-0.49, -0.25, 0.0, 0.25, 0.49];
+
+    #[test]
+    fn board_physics_round_trip() {
+        // Every board size up to a large one, odd sizes putting the board's
+        // edges off the physics grid, and every cell of each
+        for n_lanes in MIN_LANES..=40 {
+            for n_rows in MIN_ROWS..=60 {
+                let game = game(n_lanes, n_rows);
+
+                for col in 0..n_lanes as i32 {
+                    for row in 0..n_rows as i32 {
+                        let (x, y) = game.board_to_physics((col, row));
+                        assert_eq!(game.physics_to_board((x, y)), (col, row));
+
+                        // Anywhere in the cell bins to it, not just the center
+                        for dx in CELL_OFFSETS.iter() {
+                            for dy in CELL_OFFSETS.iter() {
+                                assert_eq!(
+                                    game.physics_to_board((x + dx, y + dy)),
+                                    (col, row),
+                                    "{}x{} board, offset ({}, {})",
+                                    n_lanes,
+                                    n_rows,
+                                    dx,
+                                    dy
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn board_edges() {
+        let game = game(10, 20);
 
-        spawn_tetromino(&mut commands, &mut game);
+        assert_eq!(
+            game.physics_to_board((game.left_edge_x(), game.floor_y())),
+            (0, 0)
+        );
+        assert_eq!(
+            game.physics_to_board((game.left_edge_x() - 0.01, game.floor_y() - 0.01)),
+            (-1, -1)
+        );
+        assert_eq!(game.physics_to_board((-game.left_edge_x(), 0.0)), (10, 10));
     }
 }
@@ -0,0 +1,215 @@
+//
+// Menus
+//
+// Interaction shared by every menu screen. Arrow keys and Return, the gamepad
+// D-pad and South (A) button, and mouse hover and click all move the same focus
+// highlight and choose the same buttons.
+//
+// A screen spawns its buttons with `MenuButton`, resets `MenuFocus` when it
+// (re)builds them, and reacts to `MenuChosen` events. Only one menu is shown at
+// a time. `spawn_menu_screen` builds a plain screen with a title and buttons.
+//
+
+use bevy::prelude::*;
+
+/// Component: A button in the menu currently shown
+pub struct MenuButton {
+    /// Position in the menu, from the top
+    pub index: usize,
+}
+
+/// Resource: Index of the focused button
+#[derive(Default)]
+pub struct MenuFocus(pub usize);
+
+/// Event: The button with this index was chosen
+pub struct MenuChosen(pub usize);
+
+/// Resource
+pub struct MenuMaterials {
+    pub normal: Handle<ColorMaterial>,
+    focused: Handle<ColorMaterial>,
+}
+
+impl FromWorld for MenuMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+
+        Self {
+            normal: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
+            focused: materials.add(Color::rgb(0.0, 0.5, 0.5).into()),
+        }
+    }
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<MenuChosen>()
+            .init_resource::<MenuFocus>()
+            .init_resource::<MenuMaterials>()
+            .add_system(menu_navigation.system())
+            .add_system(menu_highlight.system());
+    }
+}
+
+///
+/// Whether any gamepad just pressed the given button
+///
+pub fn gamepad_just_pressed(input: &Input<GamepadButton>, button_type: GamepadButtonType) -> bool {
+    input
+        .get_just_pressed()
+        .any(|GamepadButton(_, pressed)| *pressed == button_type)
+}
+
+///
+/// A full screen menu: a title, a subtitle and a column of buttons, centered
+/// on a translucent background. Returns the root UI node.
+///
+pub fn spawn_menu_screen(
+    commands: &mut Commands,
+    font: Handle<Font>,
+    background: Handle<ColorMaterial>,
+    menu_materials: &MenuMaterials,
+    title: &str,
+    subtitle: &str,
+    labels: &[&str],
+) -> Entity {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                // Bevy UI's y axis points up, so "reverse" means top to bottom
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: background,
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text {
+                    sections: vec![
+                        TextSection {
+                            value: format!("{}\n", title),
+                            style: TextStyle {
+                                font: font.clone(),
+                                font_size: 60.0,
+                                color: Color::WHITE,
+                            },
+                        },
+                        TextSection {
+                            value: subtitle.to_string(),
+                            style: TextStyle {
+                                font: font.clone(),
+                                font_size: 24.0,
+                                color: Color::WHITE,
+                            },
+                        },
+                    ],
+                    alignment: TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        vertical: VerticalAlign::Center,
+                    },
+                },
+                style: Style {
+                    margin: Rect {
+                        bottom: Val::Px(20.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+
+            for (index, label) in labels.iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(240.0), Val::Px(48.0)),
+                            margin: Rect::all(Val::Px(6.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: menu_materials.normal.clone(),
+                        ..Default::default()
+                    })
+                    .insert(MenuButton { index })
+                    .with_children(|button| {
+                        button.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                *label,
+                                TextStyle {
+                                    font: font.clone(),
+                                    font_size: 28.0,
+                                    color: Color::WHITE,
+                                },
+                                TextAlignment::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    });
+            }
+        })
+        .id()
+}
+
+// system
+fn menu_navigation(
+    mut input: ResMut<Input<KeyCode>>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    mut focus: ResMut<MenuFocus>,
+    mut chosen_events: EventWriter<MenuChosen>,
+    button_query: Query<&MenuButton>,
+    interaction_query: Query<(&MenuButton, &Interaction), Changed<Interaction>>,
+) {
+    let n_buttons = button_query.iter().count();
+    if n_buttons == 0 {
+        return;
+    }
+
+    if input.just_pressed(KeyCode::Down)
+        || gamepad_just_pressed(&gamepad_input, GamepadButtonType::DPadDown)
+    {
+        focus.0 = (focus.0 + 1) % n_buttons;
+    }
+    if input.just_pressed(KeyCode::Up)
+        || gamepad_just_pressed(&gamepad_input, GamepadButtonType::DPadUp)
+    {
+        focus.0 = (focus.0 + n_buttons - 1) % n_buttons;
+    }
+    if input.just_pressed(KeyCode::Return)
+        || gamepad_just_pressed(&gamepad_input, GamepadButtonType::South)
+    {
+        chosen_events.send(MenuChosen(focus.0));
+        input.reset(KeyCode::Return);
+    }
+
+    for (button, interaction) in interaction_query.iter() {
+        match interaction {
+            Interaction::Hovered => focus.0 = button.index,
+            Interaction::Clicked => chosen_events.send(MenuChosen(button.index)),
+            Interaction::None => {}
+        }
+    }
+}
+
+// system
+fn menu_highlight(
+    focus: Res<MenuFocus>,
+    menu_materials: Res<MenuMaterials>,
+    mut button_query: Query<(&MenuButton, &mut Handle<ColorMaterial>)>,
+) {
+    for (button, mut material) in button_query.iter_mut() {
+        *material = if button.index == focus.0 {
+            menu_materials.focused.clone()
+        } else {
+            menu_materials.normal.clone()
+        };
+    }
+}
@@ -0,0 +1,171 @@
+//
+// Gameplay modifiers
+//
+// Command line flags that change how the game plays: `--floor-gaps`,
+// `--conveyor <speed>`, `--magnets` and `--shrinking-board`. They are kept in
+// `Game::modifiers`, so they are part of the replay config hash and of share
+// codes, and a game started from a share code gets the modifiers it was made
+// with. The modules for each modifier check `Game::modifiers` rather than the
+// command line.
+//
+
+use bevy::prelude::*;
+
+use crate::Game;
+
+const FLOOR_GAPS: u8 = 1 << 0;
+const CONVEYOR: u8 = 1 << 1;
+const MAGNETS: u8 = 1 << 2;
+const SHRINKING_BOARD: u8 = 1 << 3;
+
+const ALL_BITS: u8 = FLOOR_GAPS | CONVEYOR | MAGNETS | SHRINKING_BOARD;
+
+/// Length of `Modifiers::to_bytes`
+pub const N_BYTES: usize = 5;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Modifiers {
+    /// See floor_gaps.rs
+    pub floor_gaps: bool,
+    /// Belt speed in blocks per second, see conveyor.rs
+    pub conveyor: Option<f32>,
+    /// See magnets.rs
+    pub magnets: bool,
+    /// See shrinking_board.rs
+    pub shrinking_board: bool,
+}
+
+impl Modifiers {
+    pub fn from_args() -> Self {
+        let conveyor = match crate::arg_value("--conveyor").map(|speed| speed.parse::<f32>()) {
+            None => None,
+            Some(Ok(speed)) if speed.is_finite() => Some(speed),
+            Some(_) => {
+                println!("Ignoring --conveyor, expected a speed in blocks per second");
+                None
+            }
+        };
+
+        Self {
+            floor_gaps: crate::has_flag("--floor-gaps"),
+            conveyor,
+            magnets: crate::has_flag("--magnets"),
+            shrinking_board: crate::has_flag("--shrinking-board"),
+        }
+    }
+
+    ///
+    /// The modifiers of the game, for plugins deciding what to add. main
+    /// inserts the `Game` before adding any plugin.
+    ///
+    pub fn of_app(app: &mut AppBuilder) -> Self {
+        app.world()
+            .get_resource::<Game>()
+            .map(|game| game.modifiers)
+            .unwrap_or_default()
+    }
+
+    fn bits(&self) -> u8 {
+        let mut bits = 0;
+        for (on, bit) in [
+            (self.floor_gaps, FLOOR_GAPS),
+            (self.conveyor.is_some(), CONVEYOR),
+            (self.magnets, MAGNETS),
+            (self.shrinking_board, SHRINKING_BOARD),
+        ]
+        .iter()
+        {
+            if *on {
+                bits |= bit;
+            }
+        }
+        bits
+    }
+
+    ///
+    /// For share codes and `config_hash`: one bit per modifier, then the
+    /// conveyor's speed as a little endian f32
+    ///
+    pub fn to_bytes(&self) -> [u8; N_BYTES] {
+        let mut bytes = [0; N_BYTES];
+        bytes[0] = self.bits();
+        bytes[1..].copy_from_slice(&self.conveyor.unwrap_or(0.0).to_le_bytes());
+        bytes
+    }
+
+    ///
+    /// The inverse of `to_bytes`. None if a bit is set that no modifier uses,
+    /// or the conveyor speed is NaN or infinite.
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (bits, speed) = bytes.split_first()?;
+        if bits & !ALL_BITS != 0 || speed.len() != N_BYTES - 1 {
+            return None;
+        }
+        let mut speed_bytes = [0; 4];
+        speed_bytes.copy_from_slice(speed);
+        let speed = f32::from_le_bytes(speed_bytes);
+        if bits & CONVEYOR != 0 && !speed.is_finite() {
+            return None;
+        }
+
+        Some(Self {
+            floor_gaps: bits & FLOOR_GAPS != 0,
+            conveyor: Some(speed).filter(|_| bits & CONVEYOR != 0),
+            magnets: bits & MAGNETS != 0,
+            shrinking_board: bits & SHRINKING_BOARD != 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let modifiers = Modifiers {
+            floor_gaps: true,
+            conveyor: Some(-0.5),
+            magnets: false,
+            shrinking_board: true,
+        };
+        assert_eq!(
+            Modifiers::from_bytes(&modifiers.to_bytes()),
+            Some(modifiers)
+        );
+
+        let none = Modifiers::default();
+        assert_eq!(none.to_bytes(), [0; N_BYTES]);
+        assert_eq!(Modifiers::from_bytes(&none.to_bytes()), Some(none));
+    }
+
+    #[test]
+    fn different_modifiers_differ() {
+        let magnets = Modifiers {
+            magnets: true,
+            ..Modifiers::default()
+        };
+        let floor_gaps = Modifiers {
+            floor_gaps: true,
+            ..Modifiers::default()
+        };
+
+        assert_ne!(magnets.to_bytes(), floor_gaps.to_bytes());
+    }
+
+    #[test]
+    fn unknown_bits() {
+        assert_eq!(Modifiers::from_bytes(&[0x80, 0, 0, 0, 0]), None);
+        assert_eq!(Modifiers::from_bytes(&[0]), None);
+    }
+
+    #[test]
+    fn non_finite_conveyor_speed() {
+        for speed in [f32::NAN, f32::INFINITY].iter() {
+            let mut bytes = [CONVEYOR, 0, 0, 0, 0];
+            bytes[1..].copy_from_slice(&speed.to_le_bytes());
+            assert_eq!(Modifiers::from_bytes(&bytes), None);
+        }
+    }
+}
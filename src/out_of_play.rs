@@ -0,0 +1,148 @@
+//
+// Out-of-play detection
+//
+// Sensors just outside the board, on both sides and below the floor, catch
+// blocks that escape the board, e.g. by being pushed over a wall. A caught
+// block is announced with a `BlockLost` event and then despawned.
+//
+// In the chaos presets, every lost block costs `Tuning::lost_block_points`,
+// see score.rs.
+//
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{Block, Game, Tuning};
+
+/// In terms of block size. How far outside the board the sensors start, so
+/// that blocks rotating at the edge of the board aren't caught.
+const SENSOR_GAP: f32 = 1.0;
+const SENSOR_THICKNESS: f32 = 1.0;
+
+/// How far the sensors extend above the top of the board
+const SENSOR_HEADROOM: f32 = 10.0;
+
+/// Event: A block left the board
+pub struct BlockLost(pub Entity);
+
+struct OutOfPlaySensor;
+
+pub struct OutOfPlayPlugin;
+
+impl Plugin for OutOfPlayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<BlockLost>()
+            .add_startup_system(setup_out_of_play_sensors.system())
+            .add_system(out_of_play_intersections.system())
+            .add_system(despawn_lost_blocks.system());
+    }
+}
+
+// startup system
+fn setup_out_of_play_sensors(mut commands: Commands, game: Res<Game>, tuning: Res<Tuning>) {
+    let side_x = -game.left_edge_x() + SENSOR_GAP + SENSOR_THICKNESS * 0.5;
+    let bottom_y = game.floor_y() - tuning.floor_block_height - SENSOR_GAP - SENSOR_THICKNESS * 0.5;
+    let top_y = -game.floor_y() + SENSOR_HEADROOM;
+
+    let side_half_height = (top_y - bottom_y) * 0.5;
+    let side_center_y = (top_y + bottom_y) * 0.5;
+    let bottom_half_width = side_x + SENSOR_THICKNESS * 0.5;
+
+    let sensors = [
+        (
+            [-side_x, side_center_y],
+            ColliderShape::cuboid(SENSOR_THICKNESS * 0.5, side_half_height),
+        ),
+        (
+            [side_x, side_center_y],
+            ColliderShape::cuboid(SENSOR_THICKNESS * 0.5, side_half_height),
+        ),
+        (
+            [0.0, bottom_y],
+            ColliderShape::cuboid(bottom_half_width, SENSOR_THICKNESS * 0.5),
+        ),
+    ];
+
+    for (position, shape) in sensors.iter() {
+        commands
+            .spawn()
+            .insert_bundle(ColliderBundle {
+                collider_type: ColliderType::Sensor,
+                shape: shape.clone(),
+                position: (*position).into(),
+                flags: ColliderFlags {
+                    active_events: ActiveEvents::INTERSECTION_EVENTS,
+                    ..ColliderFlags::default()
+                },
+                ..ColliderBundle::default()
+            })
+            .insert(OutOfPlaySensor);
+    }
+}
+
+// system
+fn out_of_play_intersections(
+    mut intersection_events: EventReader<IntersectionEvent>,
+    mut lost_events: EventWriter<BlockLost>,
+    sensor_query: Query<Entity, With<OutOfPlaySensor>>,
+    block_query: Query<Entity, With<Block>>,
+) {
+    // A block can touch two sensors at once in a corner
+    let mut lost = HashSet::new();
+
+    for event in intersection_events.iter() {
+        if !event.intersecting {
+            continue;
+        }
+
+        let entity1 = event.collider1.entity();
+        let entity2 = event.collider2.entity();
+
+        let block_entity = if sensor_query.get(entity1).is_ok() {
+            entity2
+        } else if sensor_query.get(entity2).is_ok() {
+            entity1
+        } else {
+            continue;
+        };
+
+        if block_query.get(block_entity).is_ok() && lost.insert(block_entity) {
+            lost_events.send(BlockLost(block_entity));
+        }
+    }
+}
+
+///
+/// If the whole current tetromino is lost, the next one is spawned right away
+///
+// system
+fn despawn_lost_blocks(
+    mut commands: Commands,
+    mut lost_events: EventReader<BlockLost>,
+    mut game: ResMut<Game>,
+    tuning: Res<Tuning>,
+    block_query: Query<Entity, With<Block>>,
+) {
+    let mut current_tetromino_lost = false;
+
+    for BlockLost(block_entity) in lost_events.iter() {
+        if block_query.get(*block_entity).is_err() {
+            continue;
+        }
+
+        commands.entity(*block_entity).despawn_recursive();
+
+        if game.current_tetromino_blocks.remove(block_entity) {
+            current_tetromino_lost = game.current_tetromino_blocks.is_empty();
+        }
+    }
+
+    if current_tetromino_lost {
+        for joint in game.current_tetromino_joints.drain(..) {
+            commands.entity(joint).despawn();
+        }
+        crate::spawn_tetromino(&mut commands, &mut game, &tuning);
+    }
+}
@@ -0,0 +1,91 @@
+//
+// Outline around the current tetromino
+//
+// Each block of the current tetromino gets a slightly larger, pulsing sprite
+// behind it, so the tetromino stands out even when it lands next to blocks of
+// the same color. The outline is a child of the block, so it follows it around,
+// and it is removed when the block stops being part of the current tetromino.
+//
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{Game, Tuning};
+
+/// How far the outline reaches outside the block, in pixels
+const OUTLINE_WIDTH: f32 = 3.0;
+
+/// Pulses per second
+const PULSE_FREQUENCY: f32 = 1.5;
+
+/// Just behind the block, relative to it
+const OUTLINE_Z: f32 = -0.01;
+
+/// Outline sprite, a child of the block it outlines
+struct Outline;
+
+/// Resource: The material shared by all outlines, animated in place
+struct OutlineMaterial(Handle<ColorMaterial>);
+
+pub struct OutlinePlugin;
+
+impl Plugin for OutlinePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup_outline_material.system())
+            .add_system(update_outlines.system())
+            .add_system(pulse_outlines.system());
+    }
+}
+
+// startup system
+fn setup_outline_material(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+    commands.insert_resource(OutlineMaterial(materials.add(Color::WHITE.into())));
+}
+
+// system
+fn update_outlines(
+    mut commands: Commands,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    outline_material: Res<OutlineMaterial>,
+    outline_query: Query<(Entity, &Parent), With<Outline>>,
+) {
+    let mut outlined = HashSet::new();
+
+    for (outline, parent) in outline_query.iter() {
+        if game.current_tetromino_blocks.contains(&parent.0) {
+            outlined.insert(parent.0);
+        } else {
+            commands.entity(outline).despawn_recursive();
+        }
+    }
+
+    let size = tuning.block_px_size + 2.0 * OUTLINE_WIDTH;
+
+    for block_entity in game.current_tetromino_blocks.difference(&outlined) {
+        commands.entity(*block_entity).with_children(|block| {
+            block
+                .spawn_bundle(SpriteBundle {
+                    material: outline_material.0.clone(),
+                    sprite: Sprite::new(Vec2::new(size, size)),
+                    transform: Transform::from_xyz(0.0, 0.0, OUTLINE_Z),
+                    ..Default::default()
+                })
+                .insert(Outline);
+        });
+    }
+}
+
+// system
+fn pulse_outlines(
+    time: Res<Time>,
+    outline_material: Res<OutlineMaterial>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let phase = time.seconds_since_startup() as f32 * PULSE_FREQUENCY * std::f32::consts::TAU;
+
+    if let Some(material) = materials.get_mut(&outline_material.0) {
+        material.color.set_a(0.35 + 0.25 * phase.sin());
+    }
+}
@@ -0,0 +1,350 @@
+//
+// Pausing
+//
+// The game is paused by pushing `GameState::Paused` on top of `GameState::Playing`,
+// so gameplay systems in `SystemSet::on_update(GameState::Playing)` stop running.
+// Rapier is not aware of Bevy states, so its pipeline is switched off separately.
+//
+// Quitting mid-game, from the menu or by closing the window, asks for confirmation.
+//
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::{WindowCloseRequested, WindowFocused};
+use bevy_rapier2d::prelude::*;
+
+use crate::menu::{gamepad_just_pressed, spawn_menu_screen, MenuChosen, MenuFocus, MenuMaterials};
+use crate::replay::ReplayPlayer;
+use crate::script::InputScript;
+use crate::share_code::ShareCode;
+use crate::{Game, GameState, Restart, Tuning, FONT_PATH};
+
+/// Pause automatically after this long without any input. 0 disables it.
+const DEFAULT_IDLE_PAUSE_SECONDS: f32 = 30.0;
+
+/// Resource: Why the game is paused, shown on the pause overlay
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    Manual,
+    Idle,
+    FocusLost,
+    CloseRequested,
+    GamepadDisconnected,
+}
+
+/// Resource: counts down while no keys or gamepad buttons are pressed
+struct IdleTimer(Option<Timer>);
+
+/// Root UI node of the pause overlay
+struct PauseOverlay;
+
+/// Resource: The menu page currently shown on the pause overlay
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PausePage {
+    Main,
+    ConfirmQuit,
+}
+
+impl PausePage {
+    fn items(&self) -> &'static [PauseMenuItem] {
+        match self {
+            Self::Main => &[
+                PauseMenuItem::Resume,
+                PauseMenuItem::Restart,
+                PauseMenuItem::Quit,
+            ],
+            Self::ConfirmQuit => &[PauseMenuItem::Back, PauseMenuItem::ConfirmQuit],
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PauseMenuItem {
+    Resume,
+    Restart,
+    Quit,
+    Back,
+    ConfirmQuit,
+}
+
+impl PauseMenuItem {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Resume => "Resume",
+            Self::Restart => "Restart",
+            Self::Quit => "Quit",
+            Self::Back => "Keep playing",
+            Self::ConfirmQuit => "Quit",
+        }
+    }
+}
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let idle_pause_seconds = crate::arg_value("--idle-pause")
+            .and_then(|seconds| seconds.parse().ok())
+            .unwrap_or(DEFAULT_IDLE_PAUSE_SECONDS);
+
+        app.insert_resource(PauseReason::Manual)
+            .insert_resource(PausePage::Main)
+            .insert_resource(IdleTimer(if idle_pause_seconds > 0.0 {
+                Some(Timer::from_seconds(idle_pause_seconds, false))
+            } else {
+                None
+            }))
+            .add_system(toggle_pause.system())
+            .add_system(focus_loss_detection.system())
+            .add_system(close_requested.system())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(idle_detection.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Paused).with_system(enter_pause.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Paused)
+                    .with_system(show_pause_page.system())
+                    .with_system(pause_menu_actions.system()),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Paused).with_system(exit_pause.system()));
+    }
+}
+
+///
+/// Pause the game for the given reason, unless it's already paused.
+///
+pub fn pause(state: &mut State<GameState>, pause_reason: &mut PauseReason, reason: PauseReason) {
+    if *state.current() == GameState::Playing && state.push(GameState::Paused).is_ok() {
+        *pause_reason = reason;
+    }
+}
+
+// system
+fn toggle_pause(
+    mut input: ResMut<Input<KeyCode>>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    mut state: ResMut<State<GameState>>,
+    mut pause_reason: ResMut<PauseReason>,
+    mut page: ResMut<PausePage>,
+) {
+    if !input.just_pressed(KeyCode::Escape)
+        && !gamepad_just_pressed(&gamepad_input, GamepadButtonType::Start)
+    {
+        return;
+    }
+
+    match (*state.current(), *page) {
+        (GameState::Playing, _) => pause(&mut state, &mut pause_reason, PauseReason::Manual),
+        (GameState::Paused, PausePage::ConfirmQuit) => *page = PausePage::Main,
+        (GameState::Paused, PausePage::Main) => {
+            let _ = state.pop();
+        }
+        // No game to pause, see title_screen.rs and game_over.rs
+        (GameState::Menu, _) | (GameState::GameOver, _) => {}
+    }
+
+    // Don't let a state re-run see the same key press
+    input.reset(KeyCode::Escape);
+}
+
+///
+/// Replays and scripts play unattended, and may well go without a key held
+/// for longer than the timer, so they don't count as idle.
+///
+// system
+fn idle_detection(
+    time: Res<Time>,
+    input: Res<Input<KeyCode>>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    player: Option<Res<ReplayPlayer>>,
+    script: Option<Res<InputScript>>,
+    mut idle_timer: ResMut<IdleTimer>,
+    mut state: ResMut<State<GameState>>,
+    mut pause_reason: ResMut<PauseReason>,
+) {
+    if player.is_some() || script.is_some() {
+        return;
+    }
+
+    if let Some(timer) = &mut idle_timer.0 {
+        if input.get_pressed().next().is_some() || gamepad_input.get_pressed().next().is_some() {
+            timer.reset();
+        } else if timer.tick(time.delta()).just_finished() {
+            pause(&mut state, &mut pause_reason, PauseReason::Idle);
+        }
+    }
+}
+
+// system
+fn focus_loss_detection(
+    mut focus_events: EventReader<WindowFocused>,
+    mut state: ResMut<State<GameState>>,
+    mut pause_reason: ResMut<PauseReason>,
+) {
+    if focus_events.iter().any(|event| !event.focused) {
+        pause(&mut state, &mut pause_reason, PauseReason::FocusLost);
+    }
+}
+
+///
+/// The window plugin is set up to not exit on close (see main.rs),
+/// so closing the window ends up here and asks for confirmation mid-game.
+///
+// system
+fn close_requested(
+    mut close_events: EventReader<WindowCloseRequested>,
+    mut state: ResMut<State<GameState>>,
+    mut pause_reason: ResMut<PauseReason>,
+    mut page: ResMut<PausePage>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    if close_events.iter().count() == 0 {
+        return;
+    }
+
+    match *state.current() {
+        GameState::Playing => pause(&mut state, &mut pause_reason, PauseReason::CloseRequested),
+        GameState::Paused => *page = PausePage::ConfirmQuit,
+        // Nothing would be lost
+        GameState::Menu | GameState::GameOver => app_exit_events.send(AppExit),
+    }
+}
+
+// system
+fn enter_pause(
+    mut rapier_config: ResMut<RapierConfiguration>,
+    pause_reason: Res<PauseReason>,
+    mut page: ResMut<PausePage>,
+) {
+    rapier_config.physics_pipeline_active = false;
+
+    *page = match *pause_reason {
+        PauseReason::CloseRequested => PausePage::ConfirmQuit,
+        _ => PausePage::Main,
+    };
+}
+
+///
+/// (Re)build the pause overlay whenever the page changes
+///
+// system
+fn show_pause_page(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    menu_materials: Res<MenuMaterials>,
+    mut focus: ResMut<MenuFocus>,
+    pause_reason: Res<PauseReason>,
+    page: Res<PausePage>,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    overlay_query: Query<Entity, With<PauseOverlay>>,
+) {
+    if !page.is_changed() {
+        return;
+    }
+
+    for overlay in overlay_query.iter() {
+        commands.entity(overlay).despawn_recursive();
+    }
+    focus.0 = 0;
+
+    let font = asset_server.load(FONT_PATH);
+    let (title, subtitle) = match (*page, *pause_reason) {
+        (PausePage::ConfirmQuit, _) => ("QUIT?", "The current game will be lost"),
+        (PausePage::Main, PauseReason::Idle) => ("PAUSED", "Auto-paused"),
+        (PausePage::Main, PauseReason::FocusLost) => ("PAUSED", "Window lost focus"),
+        (PausePage::Main, PauseReason::GamepadDisconnected) => (
+            "PAUSED",
+            "Controller disconnected, reconnect it to continue",
+        ),
+        (PausePage::Main, _) => ("PAUSED", ""),
+    };
+    let labels: Vec<&str> = page.items().iter().map(|item| item.label()).collect();
+
+    let overlay = spawn_menu_screen(
+        &mut commands,
+        font.clone(),
+        materials.add(Color::rgba(0.0, 0.0, 0.0, 0.5).into()),
+        &menu_materials,
+        title,
+        subtitle,
+        &labels,
+    );
+    commands.entity(overlay).insert(PauseOverlay);
+
+    // So another player can play the same game, see share_code.rs
+    let share_code = ShareCode::new(&game, &tuning).filter(|_| *page == PausePage::Main);
+    if let Some(share_code) = share_code {
+        commands.entity(overlay).with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    format!("Share code: {}", share_code),
+                    TextStyle {
+                        font,
+                        font_size: 18.0,
+                        color: Color::rgb(0.8, 0.8, 0.8),
+                    },
+                    TextAlignment::default(),
+                ),
+                style: Style {
+                    margin: Rect {
+                        top: Val::Px(20.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+        });
+    }
+}
+
+// system
+fn pause_menu_actions(
+    mut chosen_events: EventReader<MenuChosen>,
+    mut state: ResMut<State<GameState>>,
+    mut page: ResMut<PausePage>,
+    mut restart_events: EventWriter<Restart>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    let chosen = chosen_events
+        .iter()
+        .last()
+        .and_then(|MenuChosen(index)| page.items().get(*index).copied());
+
+    if let Some(item) = chosen {
+        match item {
+            PauseMenuItem::Resume => {
+                let _ = state.pop();
+            }
+            PauseMenuItem::Restart => {
+                let _ = state.pop();
+                restart_events.send(Restart);
+            }
+            PauseMenuItem::Quit => *page = PausePage::ConfirmQuit,
+            PauseMenuItem::Back => *page = PausePage::Main,
+            PauseMenuItem::ConfirmQuit => app_exit_events.send(AppExit),
+        }
+    }
+}
+
+// system
+fn exit_pause(
+    mut commands: Commands,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut idle_timer: ResMut<IdleTimer>,
+    overlay_query: Query<Entity, With<PauseOverlay>>,
+) {
+    rapier_config.physics_pipeline_active = true;
+
+    if let Some(timer) = &mut idle_timer.0 {
+        timer.reset();
+    }
+
+    for overlay in overlay_query.iter() {
+        commands.entity(overlay).despawn_recursive();
+    }
+}
@@ -0,0 +1,104 @@
+//
+// Physics guard
+//
+// A numerical blow-up in Rapier gives a body NaN or absurd positions and
+// velocities, and its contacts then drag its neighbours along. Instead of
+// letting one bad body ruin the session, this clamps bodies that are merely
+// too fast, and removes bodies that are broken or far outside the board.
+//
+// Every incident is logged. With `--dump-explosions`, the replay so far is also
+// saved, so the blow-up can be reproduced.
+//
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::replay::ReplayRecorder;
+use crate::Game;
+
+/// In blocks per second. Nothing in the game moves anywhere near this fast.
+const MAX_SANE_SPEED: f32 = 200.0;
+
+/// In radians per second
+const MAX_SANE_ANGULAR_SPEED: f32 = 200.0;
+
+/// How far outside the board a body may be, in board sizes
+const MAX_SANE_DISTANCE: f32 = 2.0;
+
+pub struct PhysicsGuardPlugin;
+
+impl Plugin for PhysicsGuardPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(guard_bodies.system());
+    }
+}
+
+// system
+fn guard_bodies(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut game: ResMut<Game>,
+    recorder: Option<Res<ReplayRecorder>>,
+    mut body_query: Query<(Entity, &RigidBodyPosition, &mut RigidBodyVelocity)>,
+) {
+    let max_x = game.n_lanes as f32 * (0.5 + MAX_SANE_DISTANCE);
+    let max_y = game.n_rows as f32 * (0.5 + MAX_SANE_DISTANCE);
+    let mut incident = false;
+
+    for (entity, position, mut velocity) in body_query.iter_mut() {
+        let translation = position.position.translation;
+        let angle = position.position.rotation.angle();
+
+        let broken = !translation.x.is_finite()
+            || !translation.y.is_finite()
+            || !angle.is_finite()
+            || !velocity.linvel.x.is_finite()
+            || !velocity.linvel.y.is_finite()
+            || !velocity.angvel.is_finite()
+            || translation.x.abs() > max_x
+            || translation.y.abs() > max_y;
+
+        if broken {
+            println!(
+                "Physics guard at {:.2}s: removing {:?} at ({}, {}) angle {} moving at ({}, {}) angular {}",
+                time.seconds_since_startup(),
+                entity,
+                translation.x,
+                translation.y,
+                angle,
+                velocity.linvel.x,
+                velocity.linvel.y,
+                velocity.angvel,
+            );
+            commands.entity(entity).despawn_recursive();
+            game.current_tetromino_blocks.remove(&entity);
+            incident = true;
+            continue;
+        }
+
+        let speed = velocity.linvel.norm();
+        if speed > MAX_SANE_SPEED || velocity.angvel.abs() > MAX_SANE_ANGULAR_SPEED {
+            println!(
+                "Physics guard at {:.2}s: slowing down {:?} moving at ({}, {}) angular {}",
+                time.seconds_since_startup(),
+                entity,
+                velocity.linvel.x,
+                velocity.linvel.y,
+                velocity.angvel,
+            );
+            if speed > MAX_SANE_SPEED {
+                velocity.linvel *= MAX_SANE_SPEED / speed;
+            }
+            velocity.angvel = velocity
+                .angvel
+                .clamp(-MAX_SANE_ANGULAR_SPEED, MAX_SANE_ANGULAR_SPEED);
+            incident = true;
+        }
+    }
+
+    if incident && crate::has_flag("--dump-explosions") {
+        if let Some(recorder) = recorder {
+            recorder.dump("explosion");
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//
+// Next piece preview
+//
+// The upcoming pieces are shown in a panel on the right side of the screen,
+// below the toasts. `--preview <count>` sets how many, from 0 (no panel) to
+// `MAX_PREVIEW`, and the panel is sized to fit.
+//
+// Showing pieces ahead of time deals them from the bag early (see
+// randomizer.rs), which doesn't change the order they come in.
+//
+
+use bevy::prelude::*;
+
+use crate::{Game, TetrominoKind};
+
+const DEFAULT_PREVIEW: usize = 3;
+const MAX_PREVIEW: usize = 5;
+
+// In pixels
+const MINI_BLOCK: f32 = 12.0;
+const SLOT_WIDTH: f32 = 5.0 * MINI_BLOCK;
+const SLOT_HEIGHT: f32 = 3.0 * MINI_BLOCK;
+const MARGIN: f32 = 10.0;
+/// Leaves room for the toasts above, see toast.rs
+const TOP: f32 = 180.0;
+
+/// Resource: Number of upcoming pieces shown
+struct PreviewCount(usize);
+
+/// Root UI node of the preview panel
+struct PreviewPanel;
+
+pub struct PreviewPlugin;
+
+impl Plugin for PreviewPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let count = match crate::arg_value("--preview").map(|count| count.parse::<usize>()) {
+            None => DEFAULT_PREVIEW,
+            Some(Ok(count)) if count <= MAX_PREVIEW => count,
+            Some(_) => {
+                println!("Ignoring --preview, expected 0 to {}", MAX_PREVIEW);
+                DEFAULT_PREVIEW
+            }
+        };
+
+        app.insert_resource(PreviewCount(count))
+            .add_system(show_preview.system());
+    }
+}
+
+///
+/// Rebuild the panel whenever a piece is spawned or the game restarts
+///
+// system
+fn show_preview(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    count: Res<PreviewCount>,
+    mut game: ResMut<Game>,
+    mut shown: Local<Option<(u64, usize)>>,
+    panel_query: Query<Entity, With<PreviewPanel>>,
+) {
+    if count.0 == 0 || *shown == Some((game.seed, game.n_pieces)) {
+        return;
+    }
+    *shown = Some((game.seed, game.n_pieces));
+
+    for panel in panel_query.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+
+    if game.block_colors.is_empty() {
+        return;
+    }
+    let kinds: Vec<TetrominoKind> = game.pieces.peek(count.0).collect();
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(
+                    Val::Px(SLOT_WIDTH + 2.0 * MARGIN),
+                    Val::Px(count.0 as f32 * SLOT_HEIGHT + 2.0 * MARGIN),
+                ),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(TOP),
+                    right: Val::Px(MARGIN),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(MARGIN)),
+                // Bevy UI's y axis points up, so "reverse" means top to bottom
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.1, 0.1, 0.1, 0.9).into()),
+            ..Default::default()
+        })
+        .insert(PreviewPanel)
+        .with_children(|panel| {
+            for kind in kinds {
+                let block_color = game.block_color(Some(kind)).unwrap();
+
+                panel
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(SLOT_WIDTH), Val::Px(SLOT_HEIGHT)),
+                            ..Default::default()
+                        },
+                        material: materials.add(Color::NONE.into()),
+                        ..Default::default()
+                    })
+                    .with_children(|slot| {
+                        for position in mini_block_positions(kind) {
+                            slot.spawn_bundle(NodeBundle {
+                                style: Style {
+                                    size: Size::new(
+                                        Val::Px(MINI_BLOCK - 1.0),
+                                        Val::Px(MINI_BLOCK - 1.0),
+                                    ),
+                                    position_type: PositionType::Absolute,
+                                    position,
+                                    ..Default::default()
+                                },
+                                material: block_color.clone(),
+                                ..Default::default()
+                            });
+                        }
+                    });
+            }
+        });
+}
+
+///
+/// Where the blocks of a piece go within its slot, centered
+///
+fn mini_block_positions(kind: TetrominoKind) -> Vec<Rect<Val>> {
+    let coords = kind.layout().coords;
+
+    let min_x = coords.iter().map(|(x, _)| *x).min().unwrap();
+    let max_x = coords.iter().map(|(x, _)| *x).max().unwrap();
+    let min_y = coords.iter().map(|(_, y)| *y).min().unwrap();
+    let max_y = coords.iter().map(|(_, y)| *y).max().unwrap();
+
+    let left = (SLOT_WIDTH - (max_x - min_x + 1) as f32 * MINI_BLOCK) * 0.5;
+    let bottom = (SLOT_HEIGHT - (max_y - min_y + 1) as f32 * MINI_BLOCK) * 0.5;
+
+    coords
+        .iter()
+        .map(|(x, y)| Rect {
+            left: Val::Px(left + (x - min_x) as f32 * MINI_BLOCK),
+            bottom: Val::Px(bottom + (y - min_y) as f32 * MINI_BLOCK),
+            ..Default::default()
+        })
+        .collect()
+}
@@ -0,0 +1,234 @@
+//
+// Hold-to-restart
+//
+// Holding R while playing restarts the game, without a trip through the pause
+// menu. A ring of dots around a small "R" fills up while the key is held.
+//
+// `--restart-hold <seconds>` sets how long to hold (default 1, 0 turns it off).
+//
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{GameState, Restart, FONT_PATH};
+
+const DEFAULT_HOLD_SECONDS: f32 = 1.0;
+
+const RESTART_KEY: KeyCode = KeyCode::R;
+
+const N_DOTS: usize = 12;
+
+// In pixels
+const RING_RADIUS: f32 = 20.0;
+const DOT_SIZE: f32 = 6.0;
+const MARGIN: f32 = 10.0;
+
+/// Resource: How long R has been held, if it's enabled
+struct RestartHold {
+    hold_seconds: f32,
+    held: Option<f32>,
+}
+
+/// Resource
+struct RestartIndicatorMaterials {
+    dim: Handle<ColorMaterial>,
+    lit: Handle<ColorMaterial>,
+}
+
+impl FromWorld for RestartIndicatorMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+
+        Self {
+            dim: materials.add(Color::rgba(1.0, 1.0, 1.0, 0.2).into()),
+            lit: materials.add(Color::WHITE.into()),
+        }
+    }
+}
+
+/// Root UI node of the indicator, shown while R is held
+struct RestartIndicator;
+
+struct RestartIndicatorDot {
+    index: usize,
+}
+
+pub struct QuickRestartPlugin;
+
+impl Plugin for QuickRestartPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let hold_seconds = crate::arg_value("--restart-hold")
+            .and_then(|seconds| seconds.parse().ok())
+            .unwrap_or(DEFAULT_HOLD_SECONDS);
+
+        if hold_seconds <= 0.0 {
+            return;
+        }
+
+        app.insert_resource(RestartHold {
+            hold_seconds,
+            held: None,
+        })
+        .init_resource::<RestartIndicatorMaterials>()
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(hold_to_restart.system())
+                .with_system(show_restart_indicator.system()),
+        )
+        .add_system_set(
+            SystemSet::on_pause(GameState::Playing).with_system(cancel_restart_hold.system()),
+        );
+    }
+}
+
+// system
+fn hold_to_restart(
+    time: Res<Time>,
+    input: Res<Input<KeyCode>>,
+    mut hold: ResMut<RestartHold>,
+    mut restart_events: EventWriter<Restart>,
+) {
+    // Keeping R down after a restart doesn't restart again
+    if input.just_pressed(RESTART_KEY) {
+        hold.held = Some(0.0);
+    }
+
+    if !input.pressed(RESTART_KEY) {
+        if hold.held.is_some() {
+            hold.held = None;
+        }
+        return;
+    }
+
+    if let Some(held) = hold.held {
+        let held = held + time.delta_seconds();
+
+        if held >= hold.hold_seconds {
+            restart_events.send(Restart);
+            hold.held = None;
+        } else {
+            hold.held = Some(held);
+        }
+    }
+}
+
+///
+/// Pausing doesn't count as letting go, but starts over
+///
+// system
+fn cancel_restart_hold(
+    mut commands: Commands,
+    mut hold: ResMut<RestartHold>,
+    indicator_query: Query<Entity, With<RestartIndicator>>,
+) {
+    hold.held = None;
+
+    for indicator in indicator_query.iter() {
+        commands.entity(indicator).despawn_recursive();
+    }
+}
+
+// system
+fn show_restart_indicator(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    hold: Res<RestartHold>,
+    materials: Res<RestartIndicatorMaterials>,
+    indicator_query: Query<Entity, With<RestartIndicator>>,
+    mut dot_query: Query<(&RestartIndicatorDot, &mut Handle<ColorMaterial>)>,
+) {
+    if !hold.is_changed() {
+        return;
+    }
+
+    let held = match hold.held {
+        Some(held) => held,
+        None => {
+            for indicator in indicator_query.iter() {
+                commands.entity(indicator).despawn_recursive();
+            }
+            return;
+        }
+    };
+
+    if indicator_query.iter().next().is_none() {
+        spawn_restart_indicator(&mut commands, &asset_server, &materials);
+        return;
+    }
+
+    let n_lit = (held / hold.hold_seconds * N_DOTS as f32) as usize;
+    for (dot, mut material) in dot_query.iter_mut() {
+        *material = if dot.index < n_lit {
+            materials.lit.clone()
+        } else {
+            materials.dim.clone()
+        };
+    }
+}
+
+fn spawn_restart_indicator(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &RestartIndicatorMaterials,
+) {
+    let size = 2.0 * RING_RADIUS + DOT_SIZE;
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(size), Val::Px(size)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(MARGIN),
+                    right: Val::Px(MARGIN),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(RestartIndicator)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "R",
+                    TextStyle {
+                        font: asset_server.load(FONT_PATH),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment::default(),
+                ),
+                ..Default::default()
+            });
+
+            // Clockwise from the top
+            for index in 0..N_DOTS {
+                let angle = index as f32 / N_DOTS as f32 * TAU;
+
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(DOT_SIZE), Val::Px(DOT_SIZE)),
+                            position_type: PositionType::Absolute,
+                            position: Rect {
+                                left: Val::Px(RING_RADIUS * (1.0 + angle.sin())),
+                                bottom: Val::Px(RING_RADIUS * (1.0 + angle.cos())),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        material: materials.dim.clone(),
+                        ..Default::default()
+                    })
+                    .insert(RestartIndicatorDot { index });
+            }
+        });
+}
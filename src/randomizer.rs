@@ -0,0 +1,90 @@
+//
+// 7-bag randomizer
+//
+// Like in modern Tetris, the seven tetromino kinds are put in a "bag" in random
+// order and dealt one by one. When the bag is empty, a new one is shuffled.
+// This guarantees that no kind is missing for long.
+//
+// Pieces can be drawn ahead of time into an upcoming queue, for previews.
+//
+// A seed deals different pieces than the uniform random picks this replaced,
+// so replays and share codes from before don't match, see
+// `SIMULATION_VERSION` in replay.rs.
+//
+
+use std::collections::VecDeque;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::TetrominoKind;
+
+pub struct PieceBag {
+    rng: StdRng,
+    /// What's left of the current bag, dealt from the back
+    bag: Vec<TetrominoKind>,
+    /// Pieces already dealt from the bag, but not yet spawned
+    upcoming: VecDeque<TetrominoKind>,
+}
+
+impl PieceBag {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            bag: vec![],
+            upcoming: VecDeque::new(),
+        }
+    }
+
+    ///
+    /// Deal these pieces before any others, e.g. to restore a position
+    ///
+    pub fn deal_first(&mut self, pieces: &[TetrominoKind]) {
+        for piece in pieces.iter().rev() {
+            self.upcoming.push_front(*piece);
+        }
+    }
+
+    ///
+    /// Take the next piece
+    ///
+    pub fn next(&mut self) -> TetrominoKind {
+        self.deal_upcoming(1);
+        self.upcoming.pop_front().unwrap()
+    }
+
+    ///
+    /// The next `n` pieces, dealing them ahead of time if needed
+    ///
+    pub fn peek(&mut self, n: usize) -> impl Iterator<Item = TetrominoKind> + '_ {
+        self.deal_upcoming(n);
+        self.upcoming.iter().take(n).copied()
+    }
+
+    ///
+    /// Pieces dealt ahead of time for previews
+    ///
+    #[cfg(feature = "debug-overlay")]
+    pub fn upcoming(&self) -> impl Iterator<Item = TetrominoKind> + '_ {
+        self.upcoming.iter().copied()
+    }
+
+    ///
+    /// The pieces left in the current bag, in the order they will be dealt
+    ///
+    #[cfg(feature = "debug-overlay")]
+    pub fn remaining_in_bag(&self) -> impl Iterator<Item = TetrominoKind> + '_ {
+        self.bag.iter().rev().copied()
+    }
+
+    fn deal_upcoming(&mut self, n: usize) {
+        while self.upcoming.len() < n {
+            if self.bag.is_empty() {
+                self.bag = TetrominoKind::ALL.to_vec();
+                self.bag.shuffle(&mut self.rng);
+            }
+            self.upcoming.push_back(self.bag.pop().unwrap());
+        }
+    }
+}
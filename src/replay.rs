@@ -0,0 +1,681 @@
+//
+// Replay recording and playback
+//
+// Rapier is stepped exactly once per frame with a fixed timestep, and all
+// randomness comes from `Game::seed`. So a replay only needs the seed plus the
+// presses and releases of the relevant keys, stamped with the frame ("tick")
+// they happened on, to reproduce a game.
+//
+// Inputs are captured from keyboard events rather than by polling the key
+// state once per tick, so a press and release within the same tick are kept,
+// in order.
+//
+// File layout (all integers little endian):
+//
+//   magic           4 bytes, "BTWR"
+//   format version  u16
+//   crate version   u8 length + utf-8 bytes
+//   seed            u64
+//   config hash     u64
+//   tick count      u32
+//   event count     u32
+//   events          u32 tick + u8 key, where the key is an index into
+//                   REPLAY_KEYS, with the top bit set for a press
+//
+// Version 1 stored one u8 bitfield of held REPLAY_KEYS per tick instead of the
+// event count and events.
+//
+// Every game is also recorded to an autosave file in the temp directory, which
+// is removed on a clean exit. If it's still there on the next launch, the game
+// crashed, and the replay is kept as a recovered file the player can play back.
+//
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::app::AppExit;
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::{ElementState, InputSystem};
+use bevy::prelude::*;
+
+use crate::board_code;
+use crate::board_grid;
+use crate::modifiers::Modifiers;
+use crate::share_code;
+use crate::toast::{Toast, ToastIcon};
+use crate::{Game, GameState, Restart, Tuning, RESTART};
+
+const MAGIC: &[u8; 4] = b"BTWR";
+
+/// Bump this whenever the file layout or the meaning of the inputs changes,
+/// and keep `Replay::decode` able to read the older versions.
+const FORMAT_VERSION: u16 = 2;
+
+/// Bump this whenever the same inputs play out differently, e.g. after a
+/// change to the pieces or the physics. It's part of `config_hash`, so older
+/// replays are rejected as recorded with different settings instead of
+/// desyncing.
+///
+/// 2: pieces come from a 7-bag instead of uniform random picks
+const SIMULATION_VERSION: u32 = 2;
+
+const PRESSED_BIT: u8 = 0x80;
+
+/// The keys recorded in a replay
+const REPLAY_KEYS: [KeyCode; 6] = [
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Down,
+    KeyCode::Up,
+    KeyCode::Z,
+    KeyCode::X,
+];
+
+/// How often an in-progress recording is written to disk. About 30 seconds.
+const FLUSH_INTERVAL_TICKS: u32 = 1800;
+
+pub struct ReplayHeader {
+    /// Version of the game that recorded the replay, for error messages
+    pub crate_version: String,
+    pub seed: u64,
+    /// Hash of everything that affects the simulation besides inputs
+    pub config_hash: u64,
+}
+
+/// A press or release of one of the `REPLAY_KEYS`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    pub tick: u32,
+    /// Index into `REPLAY_KEYS`
+    pub key: u8,
+    pub pressed: bool,
+}
+
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub n_ticks: u32,
+    /// In the order they happened
+    pub inputs: Vec<InputEvent>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    NotAReplay,
+    Truncated,
+    Corrupt,
+    UnsupportedVersion { found: u16 },
+    ConfigMismatch { crate_version: String },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::NotAReplay => write!(f, "not a replay file"),
+            Self::Truncated => write!(f, "replay file is truncated"),
+            Self::Corrupt => write!(f, "replay file is corrupt"),
+            Self::UnsupportedVersion { found } => write!(
+                f,
+                "replay format version {} is not supported (this build reads up to version {})",
+                found, FORMAT_VERSION
+            ),
+            Self::ConfigMismatch { crate_version } => write!(
+                f,
+                "replay was recorded by version {} with different game settings",
+                crate_version
+            ),
+        }
+    }
+}
+
+impl Replay {
+    pub fn new(game: &Game, tuning: &Tuning) -> Self {
+        Self {
+            header: ReplayHeader {
+                crate_version: env!("CARGO_PKG_VERSION").to_string(),
+                seed: game.seed,
+                config_hash: config_hash(game, tuning),
+            },
+            n_ticks: 0,
+            inputs: vec![],
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        Self::decode(&fs::read(path).map_err(ReplayError::Io)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        fs::write(path, self.encode()).map_err(ReplayError::Io)
+    }
+
+    ///
+    /// Check that this build simulates the game the same way as the recording build.
+    ///
+    pub fn check_compatible(&self, game: &Game, tuning: &Tuning) -> Result<(), ReplayError> {
+        if self.header.config_hash == config_hash(game, tuning) {
+            Ok(())
+        } else {
+            Err(ReplayError::ConfigMismatch {
+                crate_version: self.header.crate_version.clone(),
+            })
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let crate_version = self.header.crate_version.as_bytes();
+
+        let mut bytes = vec![];
+        bytes.extend_from_slice(MAGIC);
+        // Always written in the current layout, even if loaded from an older one
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.push(crate_version.len() as u8);
+        bytes.extend_from_slice(crate_version);
+        bytes.extend_from_slice(&self.header.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.header.config_hash.to_le_bytes());
+        bytes.extend_from_slice(&self.n_ticks.to_le_bytes());
+        bytes.extend_from_slice(&(self.inputs.len() as u32).to_le_bytes());
+        for event in &self.inputs {
+            bytes.extend_from_slice(&event.tick.to_le_bytes());
+            bytes.push(event.key | if event.pressed { PRESSED_BIT } else { 0 });
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, ReplayError> {
+        let mut reader = ByteReader(bytes);
+
+        if reader.take(MAGIC.len())? != &MAGIC[..] {
+            return Err(ReplayError::NotAReplay);
+        }
+
+        let format_version = reader.u16()?;
+        if format_version == 0 || format_version > FORMAT_VERSION {
+            return Err(ReplayError::UnsupportedVersion {
+                found: format_version,
+            });
+        }
+
+        let crate_version_len = reader.take(1)?[0] as usize;
+        let crate_version = String::from_utf8_lossy(reader.take(crate_version_len)?).into_owned();
+        let seed = reader.u64()?;
+        let config_hash = reader.u64()?;
+        let n_ticks = reader.u32()?;
+
+        let inputs = match format_version {
+            1 => bitfields_to_events(reader.take(n_ticks as usize)?),
+            _ => {
+                let n_events = reader.u32()?;
+                let mut inputs = vec![];
+                for _ in 0..n_events {
+                    let tick = reader.u32()?;
+                    let key = reader.take(1)?[0];
+
+                    if (key & !PRESSED_BIT) as usize >= REPLAY_KEYS.len() || tick >= n_ticks {
+                        return Err(ReplayError::Corrupt);
+                    }
+                    inputs.push(InputEvent {
+                        tick,
+                        key: key & !PRESSED_BIT,
+                        pressed: key & PRESSED_BIT != 0,
+                    });
+                }
+                inputs
+            }
+        };
+
+        Ok(Self {
+            header: ReplayHeader {
+                crate_version,
+                seed,
+                config_hash,
+            },
+            n_ticks,
+            inputs,
+        })
+    }
+}
+
+///
+/// Version 1 replays store which keys are held on each tick. The events are
+/// wherever that changes.
+///
+fn bitfields_to_events(bitfields: &[u8]) -> Vec<InputEvent> {
+    let mut events = vec![];
+    let mut previous = 0;
+
+    for (tick, bits) in bitfields.iter().enumerate() {
+        for key in 0..REPLAY_KEYS.len() as u8 {
+            let pressed = bits & (1 << key) != 0;
+            if pressed != (previous & (1 << key) != 0) {
+                events.push(InputEvent {
+                    tick: tick as u32,
+                    key,
+                    pressed,
+                });
+            }
+        }
+        previous = *bits;
+    }
+
+    events
+}
+
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ReplayError> {
+        if self.0.len() < n {
+            return Err(ReplayError::Truncated);
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> Result<u16, ReplayError> {
+        let mut buf = [0; 2];
+        buf.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    fn u32(&mut self) -> Result<u32, ReplayError> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn u64(&mut self) -> Result<u64, ReplayError> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+}
+
+///
+/// FNV-1a over the game settings and `SIMULATION_VERSION`. `DefaultHasher` is not stable between
+/// Rust releases, so it can't be used for something stored in files.
+///
+pub fn config_hash(game: &Game, tuning: &Tuning) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+
+    feed(&SIMULATION_VERSION.to_le_bytes());
+    feed(&(game.n_lanes as u64).to_le_bytes());
+    feed(&(game.n_rows as u64).to_le_bytes());
+    feed(&game.modifiers.to_bytes());
+    if let Some(grid) = &game.starting_board {
+        feed(grid.to_ascii().as_bytes());
+    }
+    for kind in &game.dealt_first {
+        feed(kind.letter().to_string().as_bytes());
+    }
+    for (name, value) in tuning.simulation_values() {
+        feed(name.as_bytes());
+        feed(&value.to_le_bytes());
+    }
+
+    hash
+}
+
+/// Resource: present while recording, which is always unless playing back
+pub struct ReplayRecorder {
+    replay: Replay,
+    /// `--record` target, if any
+    path: Option<PathBuf>,
+    /// Bitfield of the `REPLAY_KEYS` held according to the recorded events
+    held: u8,
+}
+
+impl ReplayRecorder {
+    fn record(&mut self, key: usize, pressed: bool) {
+        self.replay.inputs.push(InputEvent {
+            tick: self.replay.n_ticks,
+            key: key as u8,
+            pressed,
+        });
+        self.held = set_bit(self.held, key, pressed);
+    }
+
+    fn flush(&self) {
+        if let Some(path) = &self.path {
+            save_replay(&self.replay, path);
+        }
+        save_replay(&self.replay, &autosave_path());
+    }
+
+    ///
+    /// Save the replay so far next to the autosave, for debugging
+    ///
+    pub fn dump(&self, name: &str) {
+        let path = std::env::temp_dir().join(format!("bevy-tetris-workshop-{}.btwr", name));
+        save_replay(&self.replay, &path);
+        println!("Saved the replay so far to {}", path.display());
+    }
+}
+
+fn save_replay(replay: &Replay, path: &Path) {
+    if let Err(err) = replay.save(path) {
+        println!("Failed to save replay to {}: {}", path.display(), err);
+    }
+}
+
+/// Resource: a replay rescued from a crashed session
+struct RecoveredReplay(PathBuf);
+
+fn autosave_path() -> PathBuf {
+    std::env::temp_dir().join("bevy-tetris-workshop-autosave.btwr")
+}
+
+fn recovered_path() -> PathBuf {
+    std::env::temp_dir().join("bevy-tetris-workshop-recovered.btwr")
+}
+
+///
+/// An autosave left behind means the last session didn't exit cleanly.
+/// Move it out of the way before this session starts overwriting it.
+///
+fn recover_autosave(app: &mut AppBuilder) {
+    let autosave = autosave_path();
+    if !autosave.exists() {
+        return;
+    }
+
+    let recovered = recovered_path();
+    match fs::rename(&autosave, &recovered) {
+        Ok(()) => {
+            app.insert_resource(RecoveredReplay(recovered));
+        }
+        Err(err) => println!("Can't recover {}: {}", autosave.display(), err),
+    }
+}
+
+/// Resource: present while playing back a replay
+pub struct ReplayPlayer {
+    replay: Replay,
+    tick: u32,
+    /// Index of the next event to play
+    next_event: usize,
+    /// Bitfield of the `REPLAY_KEYS` held according to the events played so far
+    held: u8,
+}
+
+fn set_bit(bits: u8, i: usize, value: bool) -> u8 {
+    (bits & !(1 << i)) | ((value as u8) << i)
+}
+
+///
+/// Handle `--record <file>`, `--replay <file>` and `--code <code>`, and create the
+/// `Game` to start.
+/// Exits the process with a readable message if the replay can't be played.
+///
+pub fn init_from_args(app: &mut AppBuilder, tuning: &Tuning) -> Game {
+    recover_autosave(app);
+    let starting_board = board_grid::starting_board_from_args();
+
+    if let Some(path) = crate::arg_value("--replay") {
+        let replay = Replay::load(Path::new(&path)).unwrap_or_else(|err| {
+            eprintln!("Can't play {}: {}", path, err);
+            std::process::exit(1);
+        });
+        let mut game = Game::with_seed(replay.header.seed);
+        crate::apply_board_size_arg(&mut game);
+        game.modifiers = Modifiers::from_args();
+        game.starting_board = starting_board;
+        apply_board_code_arg(&mut game);
+
+        if let Err(err) = replay.check_compatible(&game, tuning) {
+            eprintln!("Can't play {}: {}", path, err);
+            std::process::exit(1);
+        }
+
+        app.insert_resource(ReplayPlayer {
+            replay,
+            tick: 0,
+            next_event: 0,
+            held: 0,
+        });
+        return game;
+    }
+
+    let game = match share_code::from_args() {
+        Some(share_code) => {
+            let mut game = share_code.game();
+            game.starting_board = starting_board;
+            apply_board_code_arg(&mut game);
+            if !share_code.same_tuning(&game, tuning) {
+                println!("The code was made with different tuning, the game will play differently");
+            }
+            game
+        }
+        None => {
+            let mut game = Game::default();
+            crate::apply_board_size_arg(&mut game);
+            game.modifiers = Modifiers::from_args();
+            game.starting_board = starting_board;
+            apply_board_code_arg(&mut game);
+            game
+        }
+    };
+
+    app.insert_resource(ReplayRecorder {
+        replay: Replay::new(&game, tuning),
+        path: crate::arg_value("--record").map(PathBuf::from),
+        held: 0,
+    });
+
+    game
+}
+
+///
+/// `--board-code <code>` sets the board size and the first pieces. The stack
+/// comes in through `Game::starting_board`.
+///
+fn apply_board_code_arg(game: &mut Game) {
+    if let Some(board_code) = board_code::from_args() {
+        board_code.apply(game);
+    }
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(announce_replay.system())
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                play_back_inputs.system().after(InputSystem),
+            )
+            .add_system(record_inputs.system())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Paused).with_system(flush_recording.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver).with_system(flush_recording.system()),
+            )
+            .add_system(flush_on_exit.system())
+            .add_system(restart_replay.system().after(RESTART));
+    }
+}
+
+// startup system
+fn announce_replay(
+    recorder: Option<Res<ReplayRecorder>>,
+    player: Option<Res<ReplayPlayer>>,
+    recovered: Option<Res<RecoveredReplay>>,
+    mut toasts: EventWriter<Toast>,
+) {
+    if let Some(path) = recorder
+        .as_ref()
+        .and_then(|recorder| recorder.path.as_ref())
+    {
+        toasts.send(Toast::new(
+            ToastIcon::Save,
+            format!("Recording replay to {}", path.display()),
+        ));
+    }
+    if let Some(recovered) = recovered {
+        toasts.send(Toast::new(
+            ToastIcon::Save,
+            format!(
+                "Last game didn't exit cleanly, watch it with --replay {}",
+                recovered.0.display()
+            ),
+        ));
+    }
+    if player.is_some() {
+        toasts.send(Toast::new(ToastIcon::Info, "Playing back replay"));
+    }
+}
+
+///
+/// Checks the state itself, so key events from while paused are dropped rather
+/// than recorded late.
+///
+// system
+fn record_inputs(
+    state: Res<State<GameState>>,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    input: Res<Input<KeyCode>>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+) {
+    let mut recorder = match recorder {
+        Some(recorder) if *state.current() == GameState::Playing => recorder,
+        _ => {
+            keyboard_events.iter().count();
+            return;
+        }
+    };
+
+    for event in keyboard_events.iter() {
+        let key = event
+            .key_code
+            .and_then(|key_code| REPLAY_KEYS.iter().position(|key| *key == key_code));
+
+        if let Some(key) = key {
+            recorder.record(key, event.state == ElementState::Pressed);
+        }
+    }
+
+    // Catch what the events don't tell: keys pressed or released while paused,
+    // and keys pressed by a script
+    for (key, key_code) in REPLAY_KEYS.iter().enumerate() {
+        let pressed = input.pressed(*key_code);
+        if pressed != (recorder.held & (1 << key) != 0) {
+            recorder.record(key, pressed);
+        }
+    }
+
+    recorder.replay.n_ticks += 1;
+
+    if recorder.replay.n_ticks % FLUSH_INTERVAL_TICKS == 0 {
+        recorder.flush();
+    }
+}
+
+///
+/// A restarted game has a new seed, so start recording from scratch, or
+/// stop playing back since the replay no longer applies.
+///
+// system
+fn restart_replay(
+    mut commands: Commands,
+    mut restart_events: EventReader<Restart>,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+    player: Option<Res<ReplayPlayer>>,
+) {
+    if restart_events.iter().count() == 0 {
+        return;
+    }
+
+    if let Some(mut recorder) = recorder {
+        recorder.replay = Replay::new(&game, &tuning);
+        recorder.held = 0;
+    }
+    if player.is_some() {
+        commands.remove_resource::<ReplayPlayer>();
+    }
+}
+
+///
+/// Save the autosave when play stops, in case the game is closed uncleanly
+/// from a menu
+///
+// system
+fn flush_recording(recorder: Option<Res<ReplayRecorder>>) {
+    if let Some(recorder) = recorder {
+        recorder.flush();
+    }
+}
+
+///
+/// A clean exit doesn't need the autosave
+///
+// system
+fn flush_on_exit(mut exit_events: EventReader<AppExit>, recorder: Option<Res<ReplayRecorder>>) {
+    if let (Some(_), Some(recorder)) = (exit_events.iter().next(), recorder) {
+        if let Some(path) = &recorder.path {
+            save_replay(&recorder.replay, path);
+        }
+        let _ = fs::remove_file(autosave_path());
+    }
+}
+
+// system
+fn play_back_inputs(
+    state: Res<State<GameState>>,
+    mut input: ResMut<Input<KeyCode>>,
+    player: Option<ResMut<ReplayPlayer>>,
+) {
+    // State system sets only work in the stage the state is driven from,
+    // so this one checks the state itself.
+    if *state.current() != GameState::Playing {
+        return;
+    }
+
+    let mut player = match player {
+        Some(player) => player,
+        None => return,
+    };
+    let player = &mut *player;
+    if player.tick >= player.replay.n_ticks {
+        return;
+    }
+
+    while let Some(event) = player.replay.inputs.get(player.next_event) {
+        if event.tick > player.tick {
+            break;
+        }
+
+        let key = REPLAY_KEYS[event.key as usize];
+        if event.pressed {
+            input.press(key);
+        } else {
+            input.release(key);
+        }
+        player.held = set_bit(player.held, event.key as usize, event.pressed);
+        player.next_event += 1;
+    }
+
+    // Don't let the real keyboard interfere
+    for (i, key) in REPLAY_KEYS.iter().enumerate() {
+        let held = player.held & (1 << i) != 0;
+        if held && !input.pressed(*key) {
+            input.press(*key);
+        } else if !held && input.pressed(*key) {
+            input.release(*key);
+        }
+    }
+
+    player.tick += 1;
+}
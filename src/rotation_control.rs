@@ -0,0 +1,116 @@
+//
+// Tap to rotate
+//
+// With `rotation_control_p` above 0 (the `--feel precise` preset), tapping Up
+// or Z turns the current tetromino a quarter turn counterclockwise, and
+// tapping X a quarter turn clockwise. Each block gets a `RotationTarget` at
+// the next multiple of 90 degrees that way, and a PD controller
+// applies torque until it is within `TOLERANCE` of it and has stopped turning.
+// The torque is scaled by the block's inertia, so every piece turns alike.
+//
+// While a block has a target, the rotation snap in main.rs leaves it alone.
+//
+
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{Game, GameState, Tuning};
+
+const COUNTERCLOCKWISE_KEYS: [KeyCode; 2] = [KeyCode::Up, KeyCode::Z];
+const CLOCKWISE_KEY: KeyCode = KeyCode::X;
+
+/// In radians, about 2 degrees
+const TOLERANCE: f32 = 0.035;
+
+/// In radians per second
+const MAX_SETTLED_SPIN: f32 = 0.2;
+
+/// The angle a block is being turned to, in radians
+pub struct RotationTarget(f32);
+
+pub struct RotationControlPlugin;
+
+impl Plugin for RotationControlPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(start_rotation.system())
+                .with_system(rotation_control.system()),
+        );
+    }
+}
+
+// system
+fn start_rotation(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    block_query: Query<(&RigidBodyPosition, Option<&RotationTarget>)>,
+) {
+    if tuning.rotation_control_p <= 0.0 {
+        return;
+    }
+
+    let counterclockwise = COUNTERCLOCKWISE_KEYS
+        .iter()
+        .any(|key| input.just_pressed(*key));
+    let quarter_turns = counterclockwise as i8 - input.just_pressed(CLOCKWISE_KEY) as i8;
+    if quarter_turns == 0 {
+        return;
+    }
+
+    for block_entity in &game.current_tetromino_blocks {
+        if let Ok((position, target)) = block_query.get(*block_entity) {
+            // Tapping again mid-turn turns a further quarter from the current target,
+            // or back to where it came from
+            let from = match target {
+                Some(RotationTarget(angle)) => *angle,
+                None => (position.position.rotation.angle() / FRAC_PI_2).round() * FRAC_PI_2,
+            };
+
+            commands
+                .entity(*block_entity)
+                .insert(RotationTarget(from + quarter_turns as f32 * FRAC_PI_2));
+        }
+    }
+}
+
+// system
+fn rotation_control(
+    mut commands: Commands,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    mut block_query: Query<(
+        Entity,
+        &RotationTarget,
+        &RigidBodyPosition,
+        &RigidBodyVelocity,
+        &RigidBodyMassProps,
+        &mut RigidBodyForces,
+    )>,
+) {
+    for (block_entity, target, position, velocity, mass_props, mut forces) in block_query.iter_mut()
+    {
+        let error = wrap_angle(target.0 - position.position.rotation.angle());
+
+        let done = error.abs() < TOLERANCE && velocity.angvel.abs() < MAX_SETTLED_SPIN;
+        if done || !game.current_tetromino_blocks.contains(&block_entity) {
+            commands.entity(block_entity).remove::<RotationTarget>();
+            continue;
+        }
+
+        let angular_acceleration =
+            tuning.rotation_control_p * error - tuning.rotation_control_d * velocity.angvel;
+        forces.torque += angular_acceleration * mass_props.local_mprops.principal_inertia();
+    }
+}
+
+///
+/// The same angle, between -PI and PI
+///
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
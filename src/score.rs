@@ -0,0 +1,221 @@
+//
+// Score
+//
+// Cleared lines score points, more for several at once, and more again for a
+// combo: each lock in a row that clears lines adds a bonus on top. Every
+// `LINES_PER_LEVEL` lines the level goes up, and with it the gravity on the
+// current tetromino, see `Tuning::level_gravity_step`. Points are multiplied
+// by the level.
+//
+// Blocks lost over the edge of the board (out_of_play.rs) cost
+// `Tuning::lost_block_points` each, which only the chaos presets set. A
+// collapse of the stack (collapse.rs) adds `Tuning::collapse_points`: a bonus
+// in the chaos presets and a penalty in the others. Penalties never take the
+// score below 0.
+//
+// Everything is driven by events from the simulation, so replays score the
+// same. The score is shown by hud.rs.
+//
+
+use bevy::prelude::*;
+
+use crate::collapse::Collapse;
+use crate::line_clearing::LinesCleared;
+use crate::out_of_play::BlockLost;
+use crate::toast::{Toast, ToastIcon};
+use crate::{Restart, Tuning, LINE_CLEARING, RESTART, SCORE};
+
+const LINES_PER_LEVEL: usize = 10;
+
+/// Points for clearing 1, 2, 3 or 4 lines at once
+const LINE_POINTS: [u64; 4] = [100, 300, 500, 800];
+
+/// Points per lock in the combo before this one
+const COMBO_POINTS: u64 = 50;
+
+/// Resource: Res<Score>
+pub struct Score {
+    pub points: u64,
+    pub lines: usize,
+    /// Locks in a row that cleared lines, the last one included
+    pub combo: usize,
+    /// Starts at 1
+    pub level: usize,
+}
+
+impl Default for Score {
+    fn default() -> Self {
+        Self {
+            points: 0,
+            lines: 0,
+            combo: 0,
+            level: 1,
+        }
+    }
+}
+
+impl Score {
+    ///
+    /// Gravity multiplier for the current tetromino at this level
+    ///
+    pub fn gravity_factor(&self, level_gravity_step: f32) -> f32 {
+        1.0 + level_gravity_step * (self.level - 1) as f32
+    }
+
+    fn add_lock(&mut self, lines_cleared: usize) {
+        if lines_cleared == 0 {
+            self.combo = 0;
+            return;
+        }
+
+        let line_points = LINE_POINTS[lines_cleared.min(LINE_POINTS.len()) - 1];
+        let combo_points = COMBO_POINTS * self.combo as u64;
+        self.points += (line_points + combo_points) * self.level as u64;
+
+        self.combo += 1;
+        self.lines += lines_cleared;
+        self.level = 1 + self.lines / LINES_PER_LEVEL;
+    }
+
+    ///
+    /// Add a bonus, or take away a penalty for negative `points`
+    ///
+    fn add_points(&mut self, points: f32) {
+        let points = points.round();
+        if points >= 0.0 {
+            self.points += points as u64;
+        } else {
+            self.points = self.points.saturating_sub(-points as u64);
+        }
+    }
+}
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Score>()
+            .add_system(reset_score.system().after(RESTART))
+            .add_system(update_score.system().label(SCORE).after(LINE_CLEARING));
+    }
+}
+
+// system
+fn reset_score(mut restart_events: EventReader<Restart>, mut score: ResMut<Score>) {
+    if restart_events.iter().count() > 0 {
+        *score = Score::default();
+    }
+}
+
+// system
+fn update_score(
+    mut cleared_events: EventReader<LinesCleared>,
+    mut lost_events: EventReader<BlockLost>,
+    mut collapse_events: EventReader<Collapse>,
+    tuning: Res<Tuning>,
+    mut score: ResMut<Score>,
+    mut toasts: EventWriter<Toast>,
+) {
+    for LinesCleared(lines_cleared) in cleared_events.iter() {
+        let level = score.level;
+        score.add_lock(*lines_cleared);
+
+        if score.level > level {
+            toasts.send(Toast::new(
+                ToastIcon::Info,
+                format!("Level {}!", score.level),
+            ));
+        }
+    }
+
+    for _ in lost_events.iter() {
+        score.add_points(-tuning.lost_block_points);
+    }
+
+    for _ in collapse_events.iter() {
+        score.add_points(tuning.collapse_points);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn penalties_stop_at_zero() {
+        let mut score = Score::default();
+        score.add_lock(1);
+        assert_eq!(score.points, LINE_POINTS[0]);
+
+        score.add_points(-25.0);
+        assert_eq!(score.points, LINE_POINTS[0] - 25);
+
+        score.add_points(-1000.0);
+        assert_eq!(score.points, 0);
+
+        score.add_points(40.0);
+        assert_eq!(score.points, 40);
+    }
+
+    #[test]
+    fn line_points() {
+        for (lines, points) in LINE_POINTS.iter().enumerate() {
+            let mut score = Score::default();
+            score.add_lock(lines + 1);
+            assert_eq!(score.points, *points);
+            assert_eq!(score.lines, lines + 1);
+        }
+
+        // More than four at once, e.g. with a tall board, scores like four
+        let mut score = Score::default();
+        score.add_lock(6);
+        assert_eq!(score.points, LINE_POINTS[3]);
+
+        let mut score = Score::default();
+        score.add_lock(0);
+        assert_eq!(score.points, 0);
+    }
+
+    #[test]
+    fn combo() {
+        let mut score = Score::default();
+        score.add_lock(1);
+        score.add_lock(1);
+        score.add_lock(2);
+        assert_eq!(score.combo, 3);
+        assert_eq!(
+            score.points,
+            LINE_POINTS[0] + (LINE_POINTS[0] + COMBO_POINTS) + (LINE_POINTS[1] + 2 * COMBO_POINTS)
+        );
+
+        // A lock without a clear ends the combo
+        score.add_lock(0);
+        assert_eq!(score.combo, 0);
+        let points = score.points;
+        score.add_lock(1);
+        assert_eq!(score.points, points + LINE_POINTS[0]);
+    }
+
+    #[test]
+    fn level_up_every_ten_lines() {
+        let mut score = Score::default();
+        for _ in 0..LINES_PER_LEVEL - 1 {
+            score.add_lock(1);
+            score.add_lock(0);
+        }
+        assert_eq!(score.level, 1);
+
+        score.add_lock(1);
+        assert_eq!(score.lines, LINES_PER_LEVEL);
+        assert_eq!(score.level, 2);
+
+        // Points are multiplied by the level the lock was made at
+        score.add_lock(0);
+        let points = score.points;
+        score.add_lock(1);
+        assert_eq!(score.points, points + 2 * LINE_POINTS[0]);
+
+        assert!((score.gravity_factor(0.1) - 1.1).abs() < 1e-6);
+        assert_eq!(Score::default().gravity_factor(0.1), 1.0);
+    }
+}
@@ -0,0 +1,182 @@
+//
+// Scripted input, for smoke testing and reproducing bugs
+//
+// `--script <file>` reads a list of timed inputs and feeds them into
+// `Input<KeyCode>` as if the keys were pressed on the keyboard. One input per
+// line, times in seconds from startup:
+//
+//   # Start from the title screen, nudge the first piece left, then pause
+//   t=0.5 press Return
+//   t=0.6 release Return
+//   t=1.0 press Left
+//   t=1.5 release Left
+//   t=2.0 press Escape
+//   t=2.1 release Escape
+//   t=3.0 exit
+//
+// Lines must be in time order. `exit` quits the game, so a script can run
+// unattended from start to finish.
+//
+// Time is counted in physics ticks, `IntegrationParameters::dt` per frame,
+// rather than by the wall clock. Every frame is a tick, in menus too, so a
+// script presses its keys on the same frames however fast the game runs, and
+// plays out the same every time.
+//
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+
+use bevy::app::AppExit;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy_rapier2d::rapier::dynamics::IntegrationParameters;
+
+#[derive(Clone, Copy, Debug)]
+enum ScriptAction {
+    Press(KeyCode),
+    Release(KeyCode),
+    Exit,
+}
+
+#[derive(Debug)]
+struct ScriptStep {
+    time: f64,
+    action: ScriptAction,
+}
+
+#[derive(Debug)]
+struct ScriptError {
+    line_number: usize,
+    message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line_number, self.message)
+    }
+}
+
+/// Resource: present while a script runs
+pub struct InputScript {
+    steps: VecDeque<ScriptStep>,
+    /// Physics ticks since startup
+    n_ticks: u64,
+}
+
+impl InputScript {
+    fn parse(source: &str) -> Result<Self, ScriptError> {
+        let mut steps = VecDeque::new();
+        let mut last_time = 0.0;
+
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |message: String| ScriptError {
+                line_number: i + 1,
+                message,
+            };
+
+            let mut words = line.split_whitespace();
+            let time = words
+                .next()
+                .and_then(|word| word.strip_prefix("t="))
+                .and_then(|time| time.parse::<f64>().ok())
+                .ok_or_else(|| error("expected a time like t=1.0".to_string()))?;
+            if time < last_time {
+                return Err(error(format!("t={} comes before t={}", time, last_time)));
+            }
+            last_time = time;
+
+            let action = match (words.next(), words.next()) {
+                (Some("press"), Some(key)) => {
+                    ScriptAction::Press(parse_key(key).ok_or_else(|| error(unknown_key(key)))?)
+                }
+                (Some("release"), Some(key)) => {
+                    ScriptAction::Release(parse_key(key).ok_or_else(|| error(unknown_key(key)))?)
+                }
+                (Some("exit"), None) => ScriptAction::Exit,
+                _ => return Err(error(format!("can't parse {}", line))),
+            };
+
+            steps.push_back(ScriptStep { time, action });
+        }
+
+        Ok(Self { steps, n_ticks: 0 })
+    }
+}
+
+///
+/// The keys the game reacts to, by `KeyCode` name
+///
+fn parse_key(name: &str) -> Option<KeyCode> {
+    match name {
+        "Left" => Some(KeyCode::Left),
+        "Right" => Some(KeyCode::Right),
+        "Up" => Some(KeyCode::Up),
+        "Down" => Some(KeyCode::Down),
+        "Z" => Some(KeyCode::Z),
+        "X" => Some(KeyCode::X),
+        "Escape" => Some(KeyCode::Escape),
+        "Return" => Some(KeyCode::Return),
+        _ => None,
+    }
+}
+
+fn unknown_key(name: &str) -> String {
+    format!(
+        "unknown key {}, expected Left, Right, Up, Down, Z, X, Escape or Return",
+        name
+    )
+}
+
+pub struct ScriptPlugin;
+
+impl Plugin for ScriptPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if let Some(path) = crate::arg_value("--script") {
+            let script = fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|source| InputScript::parse(&source).map_err(|err| err.to_string()))
+                .unwrap_or_else(|err| {
+                    eprintln!("Can't run script {}: {}", path, err);
+                    std::process::exit(1);
+                });
+
+            app.insert_resource(script).add_system_to_stage(
+                CoreStage::PreUpdate,
+                run_input_script.system().after(InputSystem),
+            );
+        }
+    }
+}
+
+///
+/// Runs regardless of `GameState`, so scripts can drive the pause menu too.
+///
+// system
+fn run_input_script(
+    integration_parameters: Res<IntegrationParameters>,
+    mut script: ResMut<InputScript>,
+    mut input: ResMut<Input<KeyCode>>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    let now = script.n_ticks as f64 * integration_parameters.dt as f64;
+    script.n_ticks += 1;
+
+    while let Some(step) = script.steps.front() {
+        if step.time > now {
+            break;
+        }
+
+        match step.action {
+            ScriptAction::Press(key) => input.press(key),
+            ScriptAction::Release(key) => input.release(key),
+            ScriptAction::Exit => exit_events.send(AppExit),
+        }
+        script.steps.pop_front();
+    }
+}
@@ -0,0 +1,309 @@
+//
+// Settled blocks
+//
+// Late in a game, most blocks are asleep in the stack, but Rapier still writes
+// their `Transform` every frame. A block that has been asleep for more than a
+// frame is marked `Settled` and loses its `RigidBodyPositionSync`, so the sync
+// skips it. When something wakes it up, the sync is put back. That happens
+// the frame after waking, so a woken block lags one frame behind on screen.
+//
+// With the tidy assist on (`Tuning::tidy_max_tilt` above 0), a piece that comes
+// to rest only slightly tilted is first eased into the grid at the nearest
+// right angle. The piece is turned as a whole, so its blocks keep their places
+// relative to each other, and it's left alone if that would put a block in a
+// cell that's taken. Badly placed pieces rest at larger angles and are left
+// alone too. Easing is counted in physics steps and stops while paused, so
+// replays play out the same.
+//
+// Blocks in the stack are drawn darker, to set it apart from the tetromino
+// still in motion: a tetromino's blocks when it locks, and blocks that didn't
+// come from a tetromino right away. They stay dark when something wakes them.
+//
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::dynamics::IntegrationParameters;
+use bevy_rapier2d::rapier::math::{Isometry, Rotation, Vector};
+
+use crate::board_grid::BoardGrid;
+use crate::{Block, Game, GameState, IVector, PieceLocked, Tuning};
+
+/// How long it takes to ease a block into its cell
+const TIDY_SECONDS: f32 = 0.2;
+
+/// How far off its cell a block of a turned piece may be, in terms of block
+/// size. More than that, and the piece has been pulled out of shape.
+const MAX_TIDY_OFFSET: f32 = 0.3;
+
+/// Brightness of blocks in the stack, relative to their normal color
+const STACK_BRIGHTNESS: f32 = 0.6;
+
+/// Marker: a block that has been asleep for more than a frame
+pub struct Settled;
+
+/// A block being eased into its grid cell
+struct Tidying {
+    from: Isometry<f32>,
+    to: Isometry<f32>,
+    elapsed: f32,
+}
+
+pub struct SettlePlugin;
+
+impl Plugin for SettlePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(unsettle_blocks.system())
+            .add_system(darken_stack_blocks.system())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(settle_blocks.system())
+                    .with_system(tidy_blocks.system()),
+            );
+    }
+}
+
+// system
+fn settle_blocks(
+    mut commands: Commands,
+    mut asleep_last_frame: Local<HashSet<Entity>>,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    grid: Res<BoardGrid>,
+    block_query: Query<
+        (Entity, &Block, &RigidBodyActivation, &RigidBodyPosition),
+        (Without<Settled>, Without<Tidying>),
+    >,
+) {
+    let asleep: HashSet<Entity> = block_query
+        .iter()
+        .filter(|(_, _, activation, _)| activation.sleeping)
+        .map(|(block_entity, _, _, _)| block_entity)
+        .collect();
+
+    // Blocks of a piece are tidied together. Blocks that didn't come from a
+    // tetromino are on their own. Ordered, so cells are claimed in the same
+    // order every time.
+    let mut pieces = BTreeMap::new();
+    for block_entity in asleep.intersection(&asleep_last_frame) {
+        if let Ok((_, block, _, position)) = block_query.get(*block_entity) {
+            let key = match block.piece {
+                Some(piece) => (Some(piece), None),
+                None => (None, Some(*block_entity)),
+            };
+            pieces
+                .entry(key)
+                .or_insert_with(Vec::new)
+                .push((*block_entity, position.position));
+        }
+    }
+
+    let mut claimed: HashSet<IVector> = HashSet::new();
+    for blocks in pieces.values_mut() {
+        // The first block is the pivot
+        blocks.sort_by_key(|(block_entity, _)| *block_entity);
+        let positions: Vec<Isometry<f32>> = blocks.iter().map(|(_, position)| *position).collect();
+
+        match tidy_targets(&game, &tuning, &grid, &mut claimed, &positions) {
+            Some(targets) => {
+                for ((block_entity, position), target) in blocks.iter().zip(targets) {
+                    commands.entity(*block_entity).insert(Tidying {
+                        from: *position,
+                        to: target,
+                        elapsed: 0.0,
+                    });
+                }
+            }
+            None => {
+                for (block_entity, _) in blocks.iter() {
+                    commands
+                        .entity(*block_entity)
+                        .insert(Settled)
+                        .remove::<RigidBodyPositionSync>();
+                }
+            }
+        }
+    }
+
+    *asleep_last_frame = asleep;
+}
+
+// system
+fn unsettle_blocks(
+    mut commands: Commands,
+    block_query: Query<(Entity, &RigidBodyActivation), With<Settled>>,
+) {
+    for (block_entity, activation) in block_query.iter() {
+        if !activation.sleeping {
+            commands
+                .entity(block_entity)
+                .remove::<Settled>()
+                .insert(RigidBodyPositionSync::Discrete);
+        }
+    }
+}
+
+// system
+fn darken_stack_blocks(
+    game: Res<Game>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut stack_colors: Local<HashMap<Handle<ColorMaterial>, Handle<ColorMaterial>>>,
+    mut locked_events: EventReader<PieceLocked>,
+    new_block_query: Query<(Entity, &Block), Added<Block>>,
+    mut block_query: Query<(&Block, &mut Handle<ColorMaterial>)>,
+) {
+    let stack_blocks = locked_events
+        .iter()
+        .flat_map(|PieceLocked { blocks }| blocks.iter().copied())
+        .chain(
+            new_block_query
+                .iter()
+                .filter(|(_, block)| block.piece.is_none())
+                .map(|(block_entity, _)| block_entity),
+        );
+
+    for block_entity in stack_blocks {
+        let (block, mut material) = match block_query.get_mut(block_entity) {
+            Ok(block) => block,
+            Err(_) => continue,
+        };
+        let block_color = match game.block_color(block.kind) {
+            Some(block_color) => block_color,
+            None => continue,
+        };
+
+        if !stack_colors.contains_key(&block_color) {
+            let color = match materials.get(&block_color) {
+                Some(block_color) => block_color.color,
+                None => continue,
+            };
+
+            let stack_color = materials.add(
+                Color::rgb(
+                    color.r() * STACK_BRIGHTNESS,
+                    color.g() * STACK_BRIGHTNESS,
+                    color.b() * STACK_BRIGHTNESS,
+                )
+                .into(),
+            );
+            stack_colors.insert(block_color.clone(), stack_color);
+        }
+
+        *material = stack_colors[&block_color].clone();
+    }
+}
+
+///
+/// Where the resting blocks of a piece should be eased to, if anywhere. The
+/// piece is turned to the nearest right angle about its first block, and each
+/// block goes to the cell it ends up in. Cells that are taken, by the stack or
+/// by `claimed` for other pieces, leave the piece as it is. The cells the piece
+/// goes to are added to `claimed`.
+///
+fn tidy_targets(
+    game: &Game,
+    tuning: &Tuning,
+    grid: &BoardGrid,
+    claimed: &mut HashSet<IVector>,
+    positions: &[Isometry<f32>],
+) -> Option<Vec<Isometry<f32>>> {
+    if tuning.tidy_max_tilt <= 0.0 {
+        return None;
+    }
+    let max_tilt = tuning.tidy_max_tilt.to_radians();
+
+    let pivot = positions.first()?.translation.vector;
+    let angle = positions.first()?.rotation.angle();
+    let turn = Rotation::new((angle / FRAC_PI_2).round() * FRAC_PI_2 - angle);
+
+    let current_cells: Vec<IVector> = positions
+        .iter()
+        .map(|position| {
+            let translation = position.translation;
+            game.physics_to_board((translation.x, translation.y))
+        })
+        .collect();
+
+    let mut targets = vec![];
+    let mut target_cells = HashSet::new();
+    let mut already_tidy = true;
+
+    for position in positions {
+        let angle = position.rotation.angle();
+        let snapped_angle = (angle / FRAC_PI_2).round() * FRAC_PI_2;
+        let tilt = (snapped_angle - angle).abs();
+        // Too far off to be an accident
+        if tilt > max_tilt {
+            return None;
+        }
+
+        let turned = pivot + turn * (position.translation.vector - pivot);
+        let cell = game.physics_to_board((turned.x, turned.y));
+        let (x, y) = game.board_to_physics(cell);
+        if (x - turned.x).abs() > MAX_TIDY_OFFSET || (y - turned.y).abs() > MAX_TIDY_OFFSET {
+            return None;
+        }
+
+        let taken = (grid.is_occupied(cell) && !current_cells.contains(&cell))
+            || claimed.contains(&cell)
+            || !target_cells.insert(cell);
+        if taken {
+            return None;
+        }
+
+        let offset = (x - position.translation.x).abs() + (y - position.translation.y).abs();
+        if tilt >= 1e-3 || offset >= 1e-3 {
+            already_tidy = false;
+        }
+        targets.push(Isometry::new(Vector::new(x, y), snapped_angle));
+    }
+
+    if already_tidy {
+        return None;
+    }
+
+    claimed.extend(target_cells);
+    Some(targets)
+}
+
+// system
+fn tidy_blocks(
+    mut commands: Commands,
+    integration_parameters: Res<IntegrationParameters>,
+    mut block_query: Query<(
+        Entity,
+        &mut Tidying,
+        &mut RigidBodyPosition,
+        &mut RigidBodyVelocity,
+        &mut RigidBodyActivation,
+    )>,
+) {
+    for (block_entity, mut tidying, mut position, mut velocity, mut activation) in
+        block_query.iter_mut()
+    {
+        tidying.elapsed += integration_parameters.dt;
+        let t = (tidying.elapsed / TIDY_SECONDS).min(1.0);
+
+        let translation = tidying
+            .from
+            .translation
+            .vector
+            .lerp(&tidying.to.translation.vector, t);
+        let from_angle = tidying.from.rotation.angle();
+        let angle = from_angle + (tidying.to.rotation.angle() - from_angle) * t;
+
+        position.position = Isometry::new(translation, angle);
+        position.next_position = position.position;
+        velocity.linvel = Vector::zeros();
+        velocity.angvel = 0.0;
+        // A sleeping body's colliders don't follow it
+        activation.wake_up(true);
+
+        // Once in place, the block falls asleep again and gets settled
+        if t >= 1.0 {
+            commands.entity(block_entity).remove::<Tidying>();
+        }
+    }
+}
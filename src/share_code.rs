@@ -0,0 +1,361 @@
+//
+// Share codes
+//
+// A short code like `AEXQB-7KD2M-...` describing a game, so two players can play
+// the exact same run: the pieces come from the same seed, on the same board,
+// with the same modifiers (see modifiers.rs). The code is shown on the pause
+// menu and entered with `--code <code>`.
+//
+// Tuning values are too many to fit, so the code only carries a hash of them
+// to warn when the other player's settings (e.g. `--feel`) differ.
+//
+// Layout before base32 encoding (all integers little endian):
+//
+//   version        u8
+//   seed           u64
+//   board size     u8 lanes, u8 rows
+//   modifiers      u8 bitfield, f32 conveyor speed, see `Modifiers::to_bytes`
+//   config hash    u32, the low half of the replay config hash
+//   checksum       u8, catches typos
+//
+// Version 1 had no modifiers.
+//
+// Codes are in Crockford's base32, which leaves out I, L, O and U. When
+// decoding, I and L are read as 1 and O as 0, in case they were typed in.
+//
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::modifiers::{self, Modifiers};
+use crate::replay::config_hash;
+use crate::{Game, Tuning, MIN_LANES, MIN_ROWS};
+
+const VERSION: u8 = 2;
+const N_BYTES: usize = 16 + modifiers::N_BYTES;
+
+/// Where the modifiers start in the layout above
+const MODIFIERS_START: usize = 11;
+const CONFIG_HASH_START: usize = MODIFIERS_START + modifiers::N_BYTES;
+
+/// Crockford's base32, without look-alikes like 0/O and 1/I
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Characters between dashes, for readability
+const GROUP_SIZE: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShareCode {
+    pub seed: u64,
+    pub n_lanes: u8,
+    pub n_rows: u8,
+    pub modifiers: Modifiers,
+    config_hash: u32,
+}
+
+#[derive(Debug)]
+pub enum ShareCodeError {
+    InvalidCharacter(char),
+    WrongLength,
+    Mistyped,
+    UnsupportedVersion { found: u8 },
+    UnplayableSize { n_lanes: u8, n_rows: u8 },
+    InvalidModifiers,
+}
+
+impl fmt::Display for ShareCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidCharacter(c) => write!(f, "'{}' can't be part of a share code", c),
+            Self::WrongLength => write!(f, "share code has the wrong length"),
+            Self::Mistyped => write!(f, "share code is mistyped"),
+            Self::UnsupportedVersion { found } => write!(
+                f,
+                "share code version {} is not supported (this build reads version {})",
+                found, VERSION
+            ),
+            Self::UnplayableSize { n_lanes, n_rows } => write!(
+                f,
+                "share code is for a {}x{} board, the game needs at least {}x{}",
+                n_lanes, n_rows, MIN_LANES, MIN_ROWS
+            ),
+            Self::InvalidModifiers => write!(
+                f,
+                "share code has modifiers this build doesn't know, or an invalid conveyor speed"
+            ),
+        }
+    }
+}
+
+impl ShareCode {
+    ///
+    /// The code for a game. None if the board is too big to fit in a code.
+    ///
+    pub fn new(game: &Game, tuning: &Tuning) -> Option<Self> {
+        Some(Self {
+            seed: game.seed,
+            n_lanes: u8::try_from(game.n_lanes).ok()?,
+            n_rows: u8::try_from(game.n_rows).ok()?,
+            modifiers: game.modifiers,
+            config_hash: config_hash(game, tuning) as u32,
+        })
+    }
+
+    pub fn parse(code: &str) -> Result<Self, ShareCodeError> {
+        let bytes = decode_base32(code).map_err(ShareCodeError::InvalidCharacter)?;
+        // Before the length, which depends on the version
+        match bytes.first() {
+            None => return Err(ShareCodeError::WrongLength),
+            Some(version) if *version != VERSION => {
+                return Err(ShareCodeError::UnsupportedVersion { found: *version })
+            }
+            Some(_) => {}
+        }
+        if bytes.len() != N_BYTES {
+            return Err(ShareCodeError::WrongLength);
+        }
+        if checksum(&bytes[..N_BYTES - 1]) != bytes[N_BYTES - 1] {
+            return Err(ShareCodeError::Mistyped);
+        }
+
+        let (n_lanes, n_rows) = (bytes[9], bytes[10]);
+        if (n_lanes as usize) < MIN_LANES || (n_rows as usize) < MIN_ROWS {
+            return Err(ShareCodeError::UnplayableSize { n_lanes, n_rows });
+        }
+
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&bytes[1..9]);
+        let modifiers = Modifiers::from_bytes(&bytes[MODIFIERS_START..CONFIG_HASH_START])
+            .ok_or(ShareCodeError::InvalidModifiers)?;
+        let mut config_hash = [0; 4];
+        config_hash.copy_from_slice(&bytes[CONFIG_HASH_START..CONFIG_HASH_START + 4]);
+
+        Ok(Self {
+            seed: u64::from_le_bytes(seed),
+            n_lanes,
+            n_rows,
+            modifiers,
+            config_hash: u32::from_le_bytes(config_hash),
+        })
+    }
+
+    ///
+    /// The game this code describes
+    ///
+    pub fn game(&self) -> Game {
+        Game {
+            n_lanes: self.n_lanes as usize,
+            n_rows: self.n_rows as usize,
+            modifiers: self.modifiers,
+            ..Game::with_seed(self.seed)
+        }
+    }
+
+    ///
+    /// Whether the game is played with the same tuning as where the code came from
+    ///
+    pub fn same_tuning(&self, game: &Game, tuning: &Tuning) -> bool {
+        self.config_hash == config_hash(game, tuning) as u32
+    }
+
+    fn to_bytes(&self) -> [u8; N_BYTES] {
+        let mut bytes = [0; N_BYTES];
+        bytes[0] = VERSION;
+        bytes[1..9].copy_from_slice(&self.seed.to_le_bytes());
+        bytes[9] = self.n_lanes;
+        bytes[10] = self.n_rows;
+        bytes[MODIFIERS_START..CONFIG_HASH_START].copy_from_slice(&self.modifiers.to_bytes());
+        bytes[CONFIG_HASH_START..CONFIG_HASH_START + 4]
+            .copy_from_slice(&self.config_hash.to_le_bytes());
+        bytes[N_BYTES - 1] = checksum(&bytes[..N_BYTES - 1]);
+        bytes
+    }
+}
+
+impl fmt::Display for ShareCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let encoded = encode_base32(&self.to_bytes());
+
+        for (i, group) in encoded.as_bytes().chunks(GROUP_SIZE).enumerate() {
+            if i > 0 {
+                write!(f, "-")?;
+            }
+            write!(f, "{}", String::from_utf8_lossy(group))?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// `--code <code>`. Exits the process with a readable message if the code is invalid.
+///
+pub fn from_args() -> Option<ShareCode> {
+    let code = crate::arg_value("--code")?;
+
+    match ShareCode::parse(&code) {
+        Ok(share_code) => Some(share_code),
+        Err(err) => {
+            eprintln!("Can't use code {}: {}", code, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0u8, |sum, byte| sum.rotate_left(1).wrapping_add(*byte))
+}
+
+pub fn encode_base32(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer: u32 = 0;
+    let mut n_bits = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        n_bits += 8;
+        while n_bits >= 5 {
+            n_bits -= 5;
+            encoded.push(ALPHABET[((buffer >> n_bits) & 31) as usize] as char);
+        }
+    }
+    if n_bits > 0 {
+        encoded.push(ALPHABET[((buffer << (5 - n_bits)) & 31) as usize] as char);
+    }
+
+    encoded
+}
+
+///
+/// Ignores dashes and whitespace, and accepts lower case and look-alikes of
+/// digits. Fails with the first character that isn't in the alphabet.
+///
+pub fn decode_base32(code: &str) -> Result<Vec<u8>, char> {
+    let mut bytes = vec![];
+    let mut buffer: u32 = 0;
+    let mut n_bits = 0;
+
+    for c in code.chars().filter(|c| *c != '-' && !c.is_whitespace()) {
+        let digit = match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            upper => upper,
+        };
+        let value = ALPHABET.iter().position(|a| *a as char == digit).ok_or(c)?;
+
+        buffer = (buffer << 5) | value as u32;
+        n_bits += 5;
+        if n_bits >= 8 {
+            n_bits -= 8;
+            bytes.push((buffer >> n_bits) as u8);
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code() -> ShareCode {
+        ShareCode {
+            seed: 0x0123_4567_89ab_cdef,
+            n_lanes: 10,
+            n_rows: 20,
+            modifiers: Modifiers {
+                magnets: true,
+                conveyor: Some(0.5),
+                ..Modifiers::default()
+            },
+            config_hash: 0xdead_beef,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let code = code();
+
+        assert_eq!(ShareCode::parse(&code.to_string()).unwrap(), code);
+    }
+
+    #[test]
+    fn modifiers_change_the_code() {
+        let plain = ShareCode {
+            modifiers: Modifiers::default(),
+            ..code()
+        };
+
+        assert_ne!(plain.to_string(), code().to_string());
+    }
+
+    #[test]
+    fn no_look_alikes() {
+        for c in b"ILOU".iter() {
+            assert!(!ALPHABET.contains(c));
+        }
+    }
+
+    #[test]
+    fn look_alikes_decode_as_digits() {
+        assert_eq!(decode_base32("OI").unwrap(), decode_base32("01").unwrap());
+        assert_eq!(decode_base32("ol").unwrap(), decode_base32("01").unwrap());
+    }
+
+    #[test]
+    fn mistyped() {
+        let mut typo = code().to_string().into_bytes();
+        typo[3] = if typo[3] == b'A' { b'B' } else { b'A' };
+
+        assert!(matches!(
+            ShareCode::parse(std::str::from_utf8(&typo).unwrap()),
+            Err(ShareCodeError::Mistyped)
+        ));
+    }
+
+    #[test]
+    fn older_versions() {
+        // Version 1 codes were shorter, but say so before their length is checked
+        let mut bytes = vec![1; N_BYTES - modifiers::N_BYTES];
+        let last = bytes.len() - 1;
+        bytes[last] = checksum(&bytes[..last]);
+
+        assert!(matches!(
+            ShareCode::parse(&encode_base32(&bytes)),
+            Err(ShareCodeError::UnsupportedVersion { found: 1 })
+        ));
+    }
+
+    #[test]
+    fn unplayable_sizes() {
+        for (n_lanes, n_rows) in [(0, 20), (10, 0), (3, 20), (10, 3)].iter() {
+            let code = ShareCode {
+                n_lanes: *n_lanes,
+                n_rows: *n_rows,
+                ..code()
+            };
+            assert!(matches!(
+                ShareCode::parse(&code.to_string()),
+                Err(ShareCodeError::UnplayableSize { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn invalid_conveyor_speed() {
+        for speed in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY].iter() {
+            let code = ShareCode {
+                modifiers: Modifiers {
+                    conveyor: Some(*speed),
+                    ..Modifiers::default()
+                },
+                ..code()
+            };
+            assert!(matches!(
+                ShareCode::parse(&code.to_string()),
+                Err(ShareCodeError::InvalidModifiers)
+            ));
+        }
+    }
+}
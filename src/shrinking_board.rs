@@ -0,0 +1,219 @@
+//
+// Shrinking board mode
+//
+// With `--shrinking-board`, the board's walls are replaced by moving ones, and
+// every `SHRINK_INTERVAL` seconds they slide one lane inwards over
+// `SHRINK_DURATION` seconds. `Game::n_lanes` drops by two as they start moving,
+// so the occupancy grid and new tetrominos follow the narrower board right
+// away. The walls are kinematic and shove aside whatever is in their way, and
+// blocks still outside the board once they stop are lost, see out_of_play.rs.
+//
+// When the board is too narrow to shrink again and still fit an I piece, the
+// game is over. The time survived, shown on the game over screen, is the score.
+//
+// Time is counted in physics steps, so replays play out the same. The flag is
+// one of the modifiers in modifiers.rs, so it's part of share codes, and
+// replays recorded with it can't be played back without it.
+//
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::dynamics::IntegrationParameters;
+use bevy_rapier2d::rapier::math::Isometry;
+
+use crate::game_over::{game_over, GameOverReason};
+use crate::modifiers::Modifiers;
+use crate::out_of_play::BlockLost;
+use crate::{Block, Game, GameState, Restart, Tuning, Wall, MIN_LANES, RESTART};
+
+// In seconds
+const SHRINK_INTERVAL: f32 = 60.0;
+const SHRINK_DURATION: f32 = 2.0;
+
+/// In terms of block size, for boards with open sides. Otherwise the walls are
+/// as thick as the board's own, see `Game::wall_thickness`.
+const OPEN_SIDES_WALL_THICKNESS: f32 = 0.5;
+
+/// How far the walls reach above the top of the board, in terms of block size
+const WALL_HEADROOM: f32 = 4.0;
+
+/// Resource: Present when the mode is on
+struct ShrinkingBoard {
+    /// The board's width when a game starts
+    start_lanes: usize,
+    /// Since the last shrink finished, or since the game started
+    elapsed: f32,
+    /// For how long the walls have been moving, while they are
+    shrinking_for: Option<f32>,
+}
+
+/// -1 for the left wall, 1 for the right
+struct MovingWall {
+    side: f32,
+}
+
+pub struct ShrinkingBoardPlugin;
+
+impl Plugin for ShrinkingBoardPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if !Modifiers::of_app(app).shrinking_board {
+            return;
+        }
+
+        // The board's own walls are spawned by setup_game
+        app.add_startup_system_to_stage(StartupStage::PostStartup, setup_walls.system())
+            .add_system(reset_shrinking_board.system().after(RESTART))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(shrink_board.system()),
+            );
+    }
+}
+
+// startup system
+fn setup_walls(
+    mut commands: Commands,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    wall_query: Query<Entity, With<Wall>>,
+) {
+    for wall in wall_query.iter() {
+        commands.entity(wall).despawn();
+    }
+
+    commands.insert_resource(ShrinkingBoard {
+        start_lanes: game.n_lanes,
+        elapsed: 0.0,
+        shrinking_for: None,
+    });
+
+    let material = materials.add(Color::rgb(0.5, 0.5, 0.5).into());
+    let thickness = wall_thickness(&game);
+    let height = game.n_rows as f32 + WALL_HEADROOM;
+
+    for side in [-1.0, 1.0].iter() {
+        let (x, y) = wall_position(&game, *side, 1.0);
+
+        commands
+            .spawn()
+            .insert_bundle(SpriteBundle {
+                material: material.clone(),
+                sprite: Sprite::new(Vec2::new(
+                    thickness * tuning.block_px_size,
+                    height * tuning.block_px_size,
+                )),
+                ..Default::default()
+            })
+            .insert_bundle(RigidBodyBundle {
+                body_type: RigidBodyType::KinematicPositionBased,
+                position: [x, y].into(),
+                ..RigidBodyBundle::default()
+            })
+            .insert_bundle(ColliderBundle {
+                shape: ColliderShape::cuboid(thickness * 0.5, height * 0.5),
+                ..ColliderBundle::default()
+            })
+            .insert(RigidBodyPositionSync::Discrete)
+            .insert(MovingWall { side: *side });
+    }
+}
+
+fn wall_thickness(game: &Game) -> f32 {
+    if game.wall_thickness > 0.0 {
+        game.wall_thickness
+    } else {
+        OPEN_SIDES_WALL_THICKNESS
+    }
+}
+
+///
+/// Where a wall stands, in physics coordinates, `progress` of the way into the
+/// current shrink. 1 is where it stands on a board of `game.n_lanes`.
+///
+fn wall_position(game: &Game, side: f32, progress: f32) -> (f32, f32) {
+    let half_width = game.n_lanes as f32 * 0.5 + (1.0 - progress);
+    let x = side * (half_width + wall_thickness(game) * 0.5);
+    let y = game.floor_y() + (game.n_rows as f32 + WALL_HEADROOM) * 0.5;
+
+    (x, y)
+}
+
+// system
+fn reset_shrinking_board(
+    mut restart_events: EventReader<Restart>,
+    mut game: ResMut<Game>,
+    mut board: ResMut<ShrinkingBoard>,
+    mut wall_query: Query<(&MovingWall, &mut RigidBodyPosition)>,
+) {
+    if restart_events.iter().count() == 0 {
+        return;
+    }
+
+    game.n_lanes = board.start_lanes;
+    board.elapsed = 0.0;
+    board.shrinking_for = None;
+
+    for (wall, mut position) in wall_query.iter_mut() {
+        let (x, y) = wall_position(&game, wall.side, 1.0);
+        position.position = Isometry::translation(x, y);
+        position.next_position = position.position;
+    }
+}
+
+// system
+fn shrink_board(
+    mut game: ResMut<Game>,
+    mut board: ResMut<ShrinkingBoard>,
+    integration_parameters: Res<IntegrationParameters>,
+    mut state: ResMut<State<GameState>>,
+    mut game_over_reason: ResMut<GameOverReason>,
+    mut lost_events: EventWriter<BlockLost>,
+    mut wall_query: Query<(&MovingWall, &mut RigidBodyPosition)>,
+    block_query: Query<(Entity, &RigidBodyPosition), (With<Block>, Without<MovingWall>)>,
+) {
+    let dt = integration_parameters.dt;
+
+    let progress = match board.shrinking_for {
+        None => {
+            board.elapsed += dt;
+            if board.elapsed < SHRINK_INTERVAL {
+                return;
+            }
+            board.elapsed = 0.0;
+
+            if game.n_lanes < MIN_LANES + 2 {
+                game_over(
+                    &mut state,
+                    &mut game_over_reason,
+                    GameOverReason::BoardTooNarrow,
+                );
+                return;
+            }
+
+            game.n_lanes -= 2;
+            board.shrinking_for = Some(0.0);
+            0.0
+        }
+        Some(shrinking_for) => {
+            let shrinking_for = shrinking_for + dt;
+            board.shrinking_for = Some(shrinking_for);
+            (shrinking_for / SHRINK_DURATION).min(1.0)
+        }
+    };
+
+    for (wall, mut position) in wall_query.iter_mut() {
+        let (x, y) = wall_position(&game, wall.side, progress);
+        position.next_position = Isometry::translation(x, y);
+    }
+
+    if progress >= 1.0 {
+        board.shrinking_for = None;
+
+        let half_width = game.n_lanes as f32 * 0.5;
+        for (block_entity, position) in block_query.iter() {
+            if position.position.translation.x.abs() > half_width {
+                lost_events.send(BlockLost(block_entity));
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//
+// Physics time scale, enabled with `--features debug-time-scale`
+//
+// For watching joints and blocks settle up close:
+//  * F5, F6, F7: physics at 0.25x, 1x and 4x speed
+//  * F8 while paused: advance physics by a single tick
+//
+// The speed is changed by scaling the timestep, so the simulation is not the
+// same as at normal speed, and a single step happens outside of any replay
+// tick. Neither can be recorded in a replay, so the keys do nothing while one
+// is being recorded, which is always except when playing one back with
+// `--replay`. A replay played back at another speed plays out differently
+// from the recording, which is fine for looking at how things move.
+//
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::dynamics::IntegrationParameters;
+
+use crate::replay::ReplayRecorder;
+use crate::toast::{Toast, ToastIcon};
+use crate::GameState;
+
+const SPEED_KEYS: [(KeyCode, f32); 3] =
+    [(KeyCode::F5, 0.25), (KeyCode::F6, 1.0), (KeyCode::F7, 4.0)];
+
+const STEP_KEY: KeyCode = KeyCode::F8;
+
+/// Resource: Physics timestep at 1x speed
+struct NormalTimestep(f32);
+
+/// Resource: Whether the physics pipeline was switched on for a single step
+#[derive(Default)]
+struct SingleStep(bool);
+
+pub struct TimeScalePlugin;
+
+impl Plugin for TimeScalePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<SingleStep>()
+            .add_startup_system(remember_timestep.system())
+            .add_system(change_speed.system())
+            .add_system_to_stage(CoreStage::PreUpdate, start_single_step.system())
+            .add_system_to_stage(CoreStage::PostUpdate, end_single_step.system());
+    }
+}
+
+// startup system
+fn remember_timestep(mut commands: Commands, integration_parameters: Res<IntegrationParameters>) {
+    commands.insert_resource(NormalTimestep(integration_parameters.dt));
+}
+
+// system
+fn change_speed(
+    input: Res<Input<KeyCode>>,
+    normal_timestep: Res<NormalTimestep>,
+    recorder: Option<Res<ReplayRecorder>>,
+    mut integration_parameters: ResMut<IntegrationParameters>,
+    mut toasts: EventWriter<Toast>,
+) {
+    for (key, speed) in SPEED_KEYS.iter() {
+        if input.just_pressed(*key) {
+            if recorder.is_some() {
+                toasts.send(recording_toast());
+                continue;
+            }
+
+            integration_parameters.dt = normal_timestep.0 * speed;
+            toasts.send(Toast::new(
+                ToastIcon::Info,
+                format!("Physics at {}x", speed),
+            ));
+        }
+    }
+}
+
+///
+/// Runs before the physics step, which happens in `CoreStage::Update`
+///
+// system
+fn start_single_step(
+    input: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    recorder: Option<Res<ReplayRecorder>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut single_step: ResMut<SingleStep>,
+    mut toasts: EventWriter<Toast>,
+) {
+    if *state.current() == GameState::Paused && input.just_pressed(STEP_KEY) {
+        if recorder.is_some() {
+            toasts.send(recording_toast());
+            return;
+        }
+
+        rapier_config.physics_pipeline_active = true;
+        single_step.0 = true;
+    }
+}
+
+// system
+fn end_single_step(
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut single_step: ResMut<SingleStep>,
+) {
+    if single_step.0 {
+        rapier_config.physics_pipeline_active = false;
+        single_step.0 = false;
+    }
+}
+
+fn recording_toast() -> Toast {
+    Toast::new(
+        ToastIcon::Info,
+        "Physics speed is fixed while recording a replay",
+    )
+}
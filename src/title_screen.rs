@@ -0,0 +1,124 @@
+//
+// Title screen
+//
+// The game starts in `GameState::Menu`, with the first tetromino frozen at the
+// top of the board behind the menu. Rapier is not aware of Bevy states, so its
+// pipeline is switched off while the menu is up, like while paused.
+//
+// Replays skip the menu and start playing right away.
+//
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::menu::{spawn_menu_screen, MenuChosen, MenuFocus, MenuMaterials};
+use crate::{GameState, FONT_PATH};
+
+const CONTROLS: &str = "Left and Right move, Up, Z and X rotate, Down drops";
+
+/// Root UI node of the title screen
+struct TitleScreen;
+
+#[derive(Clone, Copy)]
+enum TitleMenuItem {
+    Play,
+    Quit,
+}
+
+impl TitleMenuItem {
+    const ALL: [Self; 2] = [Self::Play, Self::Quit];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Play => "Play",
+            Self::Quit => "Quit",
+        }
+    }
+}
+
+pub struct TitleScreenPlugin;
+
+impl Plugin for TitleScreenPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Menu).with_system(show_title_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Menu).with_system(title_menu_actions.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Menu).with_system(hide_title_screen.system()),
+        );
+    }
+}
+
+///
+/// The state the game starts in
+///
+pub fn initial_state() -> GameState {
+    if crate::has_flag("--replay") {
+        GameState::Playing
+    } else {
+        GameState::Menu
+    }
+}
+
+// system
+fn show_title_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    menu_materials: Res<MenuMaterials>,
+    mut focus: ResMut<MenuFocus>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    rapier_config.physics_pipeline_active = false;
+    focus.0 = 0;
+
+    let labels: Vec<&str> = TitleMenuItem::ALL.iter().map(|item| item.label()).collect();
+
+    let screen = spawn_menu_screen(
+        &mut commands,
+        asset_server.load(FONT_PATH),
+        materials.add(Color::rgba(0.0, 0.0, 0.0, 0.5).into()),
+        &menu_materials,
+        "TETRIS",
+        CONTROLS,
+        &labels,
+    );
+    commands.entity(screen).insert(TitleScreen);
+}
+
+// system
+fn title_menu_actions(
+    mut chosen_events: EventReader<MenuChosen>,
+    mut state: ResMut<State<GameState>>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    let chosen = chosen_events
+        .iter()
+        .last()
+        .and_then(|MenuChosen(index)| TitleMenuItem::ALL.get(*index).copied());
+
+    match chosen {
+        Some(TitleMenuItem::Play) => {
+            let _ = state.set(GameState::Playing);
+        }
+        Some(TitleMenuItem::Quit) => app_exit_events.send(AppExit),
+        None => {}
+    }
+}
+
+// system
+fn hide_title_screen(
+    mut commands: Commands,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    screen_query: Query<Entity, With<TitleScreen>>,
+) {
+    rapier_config.physics_pipeline_active = true;
+
+    for screen in screen_query.iter() {
+        commands.entity(screen).despawn_recursive();
+    }
+}
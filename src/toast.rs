@@ -0,0 +1,210 @@
+//
+// Toasts: short notifications in the top right corner of the screen
+//
+// Anything that wants to tell the player something sends a `Toast` event.
+// Toasts queue up, slide in, linger for a while and fade out. At most
+// `MAX_VISIBLE` are shown at the same time.
+//
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::FONT_PATH;
+
+const MAX_VISIBLE: usize = 3;
+
+// In pixels
+const WIDTH: f32 = 320.0;
+const HEIGHT: f32 = 40.0;
+const MARGIN: f32 = 10.0;
+const ICON_SIZE: f32 = 16.0;
+
+// In seconds
+const SLIDE_IN: f32 = 0.3;
+const LINGER: f32 = 2.5;
+const FADE_OUT: f32 = 0.5;
+
+/// Event: Show a toast
+#[derive(Clone)]
+pub struct Toast {
+    pub icon: ToastIcon,
+    pub message: String,
+}
+
+impl Toast {
+    pub fn new(icon: ToastIcon, message: impl Into<String>) -> Self {
+        Self {
+            icon,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum ToastIcon {
+    Info,
+    Save,
+}
+
+impl ToastIcon {
+    fn color(&self) -> Color {
+        match self {
+            Self::Info => Color::rgb(0.3, 0.6, 1.0),
+            Self::Save => Color::rgb(0.3, 0.9, 0.4),
+        }
+    }
+}
+
+/// Resource: Toasts waiting for a free slot
+#[derive(Default)]
+struct ToastQueue(VecDeque<Toast>);
+
+/// Root UI node of a visible toast
+struct ToastView {
+    slot: usize,
+    age: f32,
+    background: Handle<ColorMaterial>,
+    icon: Handle<ColorMaterial>,
+    text: Entity,
+}
+
+pub struct ToastPlugin;
+
+impl Plugin for ToastPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<Toast>()
+            .init_resource::<ToastQueue>()
+            .add_system(queue_toasts.system())
+            .add_system(show_toasts.system())
+            .add_system(animate_toasts.system());
+    }
+}
+
+// system
+fn queue_toasts(mut toasts: EventReader<Toast>, mut queue: ResMut<ToastQueue>) {
+    queue.0.extend(toasts.iter().cloned());
+}
+
+// system
+fn show_toasts(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut queue: ResMut<ToastQueue>,
+    view_query: Query<&ToastView>,
+) {
+    let mut used_slots: Vec<usize> = view_query.iter().map(|view| view.slot).collect();
+
+    while used_slots.len() < MAX_VISIBLE {
+        let toast = match queue.0.pop_front() {
+            Some(toast) => toast,
+            None => return,
+        };
+        let slot = (0..MAX_VISIBLE)
+            .find(|slot| !used_slots.contains(slot))
+            .unwrap();
+        used_slots.push(slot);
+
+        let background = materials.add(Color::rgba(0.1, 0.1, 0.1, 0.9).into());
+        let icon = materials.add(toast.icon.color().into());
+        let mut text = None;
+
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Px(WIDTH), Val::Px(HEIGHT)),
+                    position_type: PositionType::Absolute,
+                    position: slot_position(slot, 0.0),
+                    align_items: AlignItems::Center,
+                    padding: Rect::all(Val::Px(MARGIN)),
+                    ..Default::default()
+                },
+                material: background.clone(),
+                ..Default::default()
+            })
+            .with_children(|parent| {
+                parent.spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(ICON_SIZE), Val::Px(ICON_SIZE)),
+                        margin: Rect {
+                            right: Val::Px(MARGIN),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    material: icon.clone(),
+                    ..Default::default()
+                });
+                text = Some(
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                toast.message,
+                                TextStyle {
+                                    font: asset_server.load(FONT_PATH),
+                                    font_size: 18.0,
+                                    color: Color::WHITE,
+                                },
+                                TextAlignment::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .id(),
+                );
+            })
+            .insert(ToastView {
+                slot,
+                age: 0.0,
+                background,
+                icon,
+                text: text.unwrap(),
+            });
+    }
+}
+
+// system
+fn animate_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut view_query: Query<(Entity, &mut ToastView, &mut Style)>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (entity, mut view, mut style) in view_query.iter_mut() {
+        view.age += time.delta_seconds();
+
+        if view.age > SLIDE_IN + LINGER + FADE_OUT {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let slide_in = (view.age / SLIDE_IN).min(1.0);
+        style.position = slot_position(view.slot, 1.0 - (1.0 - slide_in).powi(2));
+
+        let alpha = 1.0 - ((view.age - SLIDE_IN - LINGER) / FADE_OUT).max(0.0);
+        if let Some(material) = materials.get_mut(&view.background) {
+            material.color.set_a(alpha * 0.9);
+        }
+        if let Some(material) = materials.get_mut(&view.icon) {
+            material.color.set_a(alpha);
+        }
+        if let Ok(mut text) = text_query.get_mut(view.text) {
+            for section in text.sections.iter_mut() {
+                section.style.color.set_a(alpha);
+            }
+        }
+    }
+}
+
+///
+/// Where a toast in the given slot goes. `visible` goes from 0 (just outside
+/// the right edge of the screen) to 1 (fully slid in).
+///
+fn slot_position(slot: usize, visible: f32) -> Rect<Val> {
+    Rect {
+        top: Val::Px(MARGIN + slot as f32 * (HEIGHT + MARGIN)),
+        right: Val::Px(MARGIN - (1.0 - visible) * (WIDTH + MARGIN)),
+        ..Default::default()
+    }
+}
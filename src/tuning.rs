@@ -0,0 +1,237 @@
+//
+// Tuning values
+//
+// These used to be constants in main.rs. They live in a reflected resource so
+// they can be inspected and changed while the game runs, and overridden without
+// recompiling: `--tuning <file>` reads a preset of `name = value` lines using the
+// field names below, and `--save-tuning <file>` writes the values in effect.
+//
+// `--feel <name>` picks the starting point before overrides are applied:
+//  * `chaos` (default): blocks spin freely
+//  * `playable`: blocks resist spinning, settle on the nearest right angle,
+//    and are tidied into their grid cells when they come to rest nearly straight
+//  * `precise`: like `playable`, but Left and Right steer towards a set speed
+//    rather than push with a set force, and Up turns a quarter turn per tap
+//  * `ice` (chaos content): like `chaos`, but blocks and floor are nearly
+//    frictionless, so the stack never stops sliding, and pushes are stronger
+//
+// Losing blocks over the edge of the board only costs points in the chaos
+// presets, `chaos` and `ice`, where the stack is expected to fall apart. There,
+// a collapse of the stack scores a bonus; in `playable` and `precise` it costs
+// points instead.
+//
+
+use std::fs;
+
+use bevy::prelude::*;
+use bevy::reflect::Struct;
+
+/// Tuning values left out of `simulation_values`
+const NOT_SIMULATED: [&str; 3] = ["block_px_size", "lost_block_points", "collapse_points"];
+
+/// Resource: Res<Tuning>
+#[derive(Clone, Debug, Reflect)]
+pub struct Tuning {
+    /// Size of a block on screen, in pixels
+    pub block_px_size: f32,
+    /// In terms of block size
+    pub floor_block_height: f32,
+    pub block_linear_damping: f32,
+    pub block_angular_damping: f32,
+    pub block_friction: f32,
+    pub floor_friction: f32,
+    pub movement_force: f32,
+    /// Torque on each block of the current tetromino while a rotation key is held
+    pub torque: f32,
+    /// Speed limit for the current tetromino, in blocks per second.
+    /// Without it, holding a key from spawn keeps accumulating speed.
+    pub max_horizontal_velocity: f32,
+    pub max_vertical_velocity: f32,
+    /// In radians per second
+    pub max_angular_velocity: f32,
+    /// Gains of the velocity controller, see velocity_control.rs.
+    /// A `velocity_control_p` of 0 pushes with `movement_force` instead.
+    pub velocity_control_p: f32,
+    pub velocity_control_d: f32,
+    /// In blocks per second, for the velocity controller
+    pub target_horizontal_velocity: f32,
+    /// Gravity multiplier while Down is held. `inf` drops the tetromino as fast
+    /// as `max_vertical_velocity` allows, but it can still slide.
+    pub soft_drop_factor: f32,
+    /// A new tetromino starts with `gravity_ramp_start` times gravity, which
+    /// ramps up to full gravity over this many seconds. 0 turns the ramp off.
+    pub gravity_ramp_seconds: f32,
+    pub gravity_ramp_start: f32,
+    /// Extra gravity on the current tetromino per level above the first, as a
+    /// fraction of normal gravity, see score.rs
+    pub level_gravity_step: f32,
+    /// Torque per radian pulling the current tetromino towards the nearest
+    /// multiple of 90 degrees. 0 turns rotation assist off.
+    pub rotation_snap: f32,
+    /// Gains of the tap to rotate controller, see rotation_control.rs.
+    /// A `rotation_control_p` of 0 turns it off.
+    pub rotation_control_p: f32,
+    pub rotation_control_d: f32,
+    /// How strongly gravity adapts to the player, see adaptive_difficulty.rs.
+    /// 0 turns adaptive difficulty off.
+    pub adaptive_difficulty: f32,
+    /// Blocks coming to rest tilted less than this many degrees are eased
+    /// into their grid cell, see settle.rs. 0 turns the tidy assist off.
+    pub tidy_max_tilt: f32,
+    /// Points each block lost over the edge of the board costs, see score.rs.
+    /// 0 turns the penalty off.
+    pub lost_block_points: f32,
+    /// Points for a collapse of the stack, see collapse.rs. Negative for a
+    /// penalty, 0 for neither.
+    pub collapse_points: f32,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            block_px_size: 30.0,
+            floor_block_height: 2.0,
+            block_linear_damping: 1.0,
+            block_angular_damping: 0.0,
+            // Rapier's default
+            block_friction: 0.5,
+            floor_friction: 0.5,
+            movement_force: 20.0,
+            torque: 20.0,
+            max_horizontal_velocity: 6.0,
+            max_vertical_velocity: 20.0,
+            max_angular_velocity: 4.0,
+            soft_drop_factor: 5.0,
+            gravity_ramp_seconds: 0.0,
+            gravity_ramp_start: 0.2,
+            level_gravity_step: 0.1,
+            velocity_control_p: 0.0,
+            velocity_control_d: 0.0,
+            target_horizontal_velocity: 4.0,
+            rotation_snap: 0.0,
+            rotation_control_p: 0.0,
+            rotation_control_d: 0.0,
+            adaptive_difficulty: 0.0,
+            tidy_max_tilt: 0.0,
+            lost_block_points: 25.0,
+            collapse_points: 200.0,
+        }
+    }
+}
+
+impl Tuning {
+    ///
+    /// The `--feel` preset with `--tuning` overrides applied
+    ///
+    pub fn from_args() -> Self {
+        let mut tuning = match crate::arg_value("--feel").as_deref() {
+            None | Some("chaos") => Self::default(),
+            Some("playable") => Self::playable(),
+            Some("precise") => Self::precise(),
+            Some("ice") => Self::ice(),
+            Some(other) => {
+                println!(
+                    "Unknown --feel {}, expected chaos, playable, precise or ice",
+                    other
+                );
+                Self::default()
+            }
+        };
+
+        if let Some(path) = crate::arg_value("--tuning") {
+            match fs::read_to_string(&path) {
+                Ok(preset) => tuning.apply_preset(&preset),
+                Err(err) => println!("Can't read tuning preset {}: {}", path, err),
+            }
+        }
+
+        if let Some(path) = crate::arg_value("--save-tuning") {
+            if let Err(err) = fs::write(&path, tuning.to_preset()) {
+                println!("Can't save tuning preset {}: {}", path, err);
+            }
+        }
+
+        tuning
+    }
+
+    fn playable() -> Self {
+        Self {
+            block_angular_damping: 5.0,
+            rotation_snap: 10.0,
+            tidy_max_tilt: 5.0,
+            lost_block_points: 0.0,
+            collapse_points: -200.0,
+            ..Self::default()
+        }
+    }
+
+    fn precise() -> Self {
+        Self {
+            velocity_control_p: 10.0,
+            velocity_control_d: 0.05,
+            rotation_control_p: 40.0,
+            rotation_control_d: 12.0,
+            ..Self::playable()
+        }
+    }
+
+    fn ice() -> Self {
+        Self {
+            block_friction: 0.02,
+            floor_friction: 0.02,
+            movement_force: 40.0,
+            ..Self::default()
+        }
+    }
+
+    fn apply_preset(&mut self, preset: &str) {
+        let lines = preset
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        for line in lines {
+            let mut parts = line.splitn(2, '=').map(str::trim);
+
+            match (parts.next(), parts.next().map(str::parse::<f32>)) {
+                (Some(name), Some(Ok(value))) => {
+                    match self
+                        .field_mut(name)
+                        .and_then(|field| field.downcast_mut::<f32>())
+                    {
+                        Some(field) => *field = value,
+                        None => println!("Unknown tuning value: {}", name),
+                    }
+                }
+                _ => println!("Can't parse tuning line: {}", line),
+            }
+        }
+    }
+
+    fn to_preset(&self) -> String {
+        (0..self.field_len())
+            .filter_map(|i| {
+                let value = self.field_at(i)?.downcast_ref::<f32>()?;
+                Some(format!("{} = {}\n", self.name_at(i)?, value))
+            })
+            .collect()
+    }
+
+    ///
+    /// The values that affect the simulation, by name. Used to tell whether a
+    /// replay was recorded with the same tuning.
+    ///
+    pub fn simulation_values(&self) -> impl Iterator<Item = (&str, f32)> + '_ {
+        (0..self.field_len()).filter_map(move |i| {
+            let name = self.name_at(i)?;
+            let value = *self.field_at(i)?.downcast_ref::<f32>()?;
+
+            // Purely visual, or only scoring
+            if NOT_SIMULATED.contains(&name) {
+                None
+            } else {
+                Some((name, value))
+            }
+        })
+    }
+}
@@ -0,0 +1,67 @@
+//
+// Velocity controlled movement
+//
+// By default, Left and Right push the current tetromino with a fixed force
+// (`tetromino_movement` in main.rs), so how fast it goes depends on its mass,
+// friction and how long the key has been held. With `velocity_control_p` above
+// 0 (the `--feel precise` preset), this controller takes over instead: while a
+// key is held, it pushes each block towards `target_horizontal_velocity` with a
+// PD controller on the velocity error. The force is scaled by the block's mass,
+// so every piece handles the same.
+//
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::dynamics::IntegrationParameters;
+
+use crate::{Game, GameState, Tuning, MOVEMENT};
+
+pub struct VelocityControlPlugin;
+
+impl Plugin for VelocityControlPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(velocity_controlled_movement.system().label(MOVEMENT)),
+        );
+    }
+}
+
+// system
+fn velocity_controlled_movement(
+    input: Res<Input<KeyCode>>,
+    game: Res<Game>,
+    tuning: Res<Tuning>,
+    integration_parameters: Res<IntegrationParameters>,
+    mut last_errors: Local<HashMap<Entity, f32>>,
+    mut block_query: Query<(
+        &RigidBodyVelocity,
+        &RigidBodyMassProps,
+        &mut RigidBodyForces,
+    )>,
+) {
+    let movement = input.pressed(KeyCode::Right) as i8 - input.pressed(KeyCode::Left) as i8;
+
+    if tuning.velocity_control_p <= 0.0 || movement == 0 {
+        last_errors.clear();
+        return;
+    }
+
+    let target = movement as f32 * tuning.target_horizontal_velocity;
+    let dt = integration_parameters.dt;
+
+    last_errors.retain(|block_entity, _| game.current_tetromino_blocks.contains(block_entity));
+
+    for block_entity in &game.current_tetromino_blocks {
+        if let Ok((velocity, mass_props, mut forces)) = block_query.get_mut(*block_entity) {
+            let error = target - velocity.linvel.x;
+            let last_error = last_errors.insert(*block_entity, error).unwrap_or(error);
+            let acceleration = tuning.velocity_control_p * error
+                + tuning.velocity_control_d * (error - last_error) / dt;
+
+            forces.force = Vec2::new(acceleration * mass_props.local_mprops.mass(), 0.0).into();
+        }
+    }
+}